@@ -1,4 +1,4 @@
-use osrm_client::{NearestRequestBuilder, Location, Coordinates, TransportationMode, Client};
+use osrm_client::{NearestRequestBuilder, Location, TransportationMode, Client};
 
 
 #[tokio::main]
@@ -7,7 +7,7 @@ async fn main() -> Result<(), osrm_client::Error>{
     
     let req = NearestRequestBuilder::default()
         .profile(TransportationMode::Foot)
-        .coordinates(Coordinates::Single(Location::new(2.290253, 48.8583701)))
+        .coordinates(Location::new(2.290253, 48.85837))
         .build()
         .unwrap();
     
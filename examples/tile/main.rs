@@ -7,11 +7,7 @@ use osrm_client::{Client, TileRequestBuilder};
 #[tokio::main]
 async fn main() -> Result<()>{
     let client = Client::default();
-    let (x, y) = (1310.0, 3166.0);
-    let req = TileRequestBuilder::default()
-        .x(x)
-        .y(y)
-        .zoom(13)
+    let req = TileRequestBuilder::from_lat_lon(37.7707, -122.4097, 13)?
         .build()?;
     
     let rsp = req.send(&client).await?.to_vec();
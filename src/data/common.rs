@@ -1,26 +1,73 @@
 //! This module comprises a representation of the data that is shared accross
 //! all OSRM services.
 
+use std::borrow::Cow;
+
 use displaythis::Display;
 use serde::{Serialize, Deserialize};
 
+use crate::{Error, Decimal, to_gpx};
+
 
 /// Mode of transportation
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
+/// 
+/// It is displayed as the name of the profile in the urls (`as_str`, e.g. "car")
+/// but serialized as the mode OSRM reports in the route steps (`travel_mode`, 
+/// e.g. "driving"): the two names differ on purpose since the latter is what 
+/// the responses hold. Both names are accepted when deserializing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(into = "&'static str", try_from = "String")]
 pub enum TransportationMode {
     /// Travelling by car
-    #[display("car")]
-    #[serde(rename="driving")]
     Car, 
     /// Travelling by bike
-    #[display("bike")]
-    #[serde(rename="bike")]
     Bike, 
     /// Travelling on bare foot
-    #[display("foot")]
-    #[serde(rename="foot")]
     Foot,
 }
+impl TransportationMode {
+    /// The name of the profile, as it appears in the url of the requests.
+    /// This is the single source of truth for the `Display` implementation.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Car  => "car",
+            Self::Bike => "bike",
+            Self::Foot => "foot",
+        }
+    }
+    /// The mode OSRM reports in the route steps travelled with this profile.
+    /// This is the single source of truth for the serialized form.
+    pub fn travel_mode(&self) -> &'static str {
+        match self {
+            Self::Car  => "driving",
+            Self::Bike => "cycling",
+            Self::Foot => "walking",
+        }
+    }
+    /// All the modes of transportation, so as to run the same query for each of them
+    pub fn all() -> [TransportationMode; 3] {
+        [Self::Car, Self::Bike, Self::Foot]
+    }
+}
+impl std::fmt::Display for TransportationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+impl From<TransportationMode> for &'static str {
+    fn from(mode: TransportationMode) -> Self {
+        mode.travel_mode()
+    }
+}
+impl TryFrom<String> for TransportationMode {
+    type Error = Error;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::all().into_iter()
+            .find(|mode| name == mode.travel_mode() || name == mode.as_str())
+            .ok_or_else(|| Error::ParseError(format!("mode of transportation '{name}'")))
+    }
+}
 
 /// Route geometry format (influences overview and per step)
 #[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
@@ -38,8 +85,7 @@ pub enum Geometries {
 
 /// The location of a point anywhere on earth. The order of the fields is
 /// longitude, latitude
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
-#[display("{longitude},{latitude}")]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Location{
     pub longitude: f32, 
     pub latitude: f32
@@ -48,6 +94,189 @@ impl Location {
     pub fn new(longitude: f32, latitude: f32) -> Self {
         Self { longitude, latitude }
     }
+    /// Projects the location onto Web Mercator (EPSG:3857). The result is 
+    /// the (x, y) pair of coordinates, in meters.
+    pub fn to_web_mercator(&self) -> (f64, f64) {
+        let lon = f64::from(self.longitude).to_radians();
+        let lat = f64::from(self.latitude).to_radians();
+        let x = EARTH_RADIUS * lon;
+        let y = EARTH_RADIUS * (std::f64::consts::FRAC_PI_4 + lat / 2.0).tan().ln();
+        (x, y)
+    }
+    /// Creates a location from its (x, y) Web Mercator (EPSG:3857) coordinates, 
+    /// in meters.
+    pub fn from_web_mercator(x: f64, y: f64) -> Self {
+        let lon = (x / EARTH_RADIUS).to_degrees();
+        let lat = (2.0 * (y / EARTH_RADIUS).exp().atan() - std::f64::consts::FRAC_PI_2).to_degrees();
+        Self::new(lon as f32, lat as f32)
+    }
+    /// The great-circle distance (in meters) between this location and `other`,
+    /// as computed with the haversine formula.
+    pub fn haversine_distance(&self, other: &Location) -> f32 {
+        let (lat1, lat2) = (f64::from(self.latitude).to_radians(), f64::from(other.latitude).to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (f64::from(other.longitude) - f64::from(self.longitude)).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        (2.0 * MEAN_EARTH_RADIUS * a.sqrt().asin()) as f32
+    }
+    /// The (x, y) position of the location, in pixels, on the world map rendered
+    /// with 256x256 pixel tiles at the given zoom level. The origin of the 
+    /// pixel coordinates is the top left corner (180 °W, 85.0511 °N).
+    pub fn to_pixel(&self, zoom: u8) -> (f64, f64) {
+        let size = TILE_SIZE * 2_f64.powi(i32::from(zoom));
+        let (x, y) = self.to_web_mercator();
+        let half = std::f64::consts::PI * EARTH_RADIUS;
+        ((x + half) / (2.0 * half) * size, (half - y) / (2.0 * half) * size)
+    }
+    /// Snaps the location to the nearest node of a grid whose cells measure
+    /// `precision_meters` on each side. The latitude is rounded first, and the
+    /// longitude is then rounded along the parallel of the rounded latitude: 
+    /// hence all the locations within one cell snap to the very same node. 
+    /// This is typically used to key route caches on gridded coordinates.
+    /// 
+    /// The trade-off is accuracy: the snapped location may be up to 
+    /// `precision_meters / √2` meters away from the original one, which might
+    /// be enough to snap onto another road. A non positive precision leaves 
+    /// the location unchanged.
+    pub fn snap_to_grid(&self, precision_meters: f32) -> Location {
+        if precision_meters <= 0.0 {
+            return *self;
+        }
+        let meters_per_degree = MEAN_EARTH_RADIUS.to_radians();
+        let lat_step = f64::from(precision_meters) / meters_per_degree;
+        let latitude = (f64::from(self.latitude) / lat_step).round() * lat_step;
+        // close to the poles, a single cell spans all longitudes
+        let lon_step = (lat_step / latitude.to_radians().cos()).min(360.0);
+        let longitude = (f64::from(self.longitude) / lon_step).round() * lon_step;
+        Location::new(longitude as f32, latitude as f32)
+    }
+    /// Formats the location as degrees, minutes and seconds (latitude first), 
+    /// e.g. `50°51'32.9"N 4°30'57.9"E`. The seconds are rounded to one decimal.
+    pub fn to_dms(&self) -> String {
+        format!("{} {}", dms(self.latitude, 'N', 'S'), dms(self.longitude, 'E', 'W'))
+    }
+    /// Parses a location formatted as degrees, minutes and seconds (see `to_dms`).
+    /// The latitude and longitude are told apart by their hemisphere letter, 
+    /// hence they may come in any order. The minutes and seconds may be omitted
+    /// and the degrees may be fractional (e.g. `50.5°N 4°30'E`).
+    pub fn from_dms(dms: &str) -> Result<Self, Error> {
+        let invalid = || Error::ParseError(format!("dms location '{dms}' (expected e.g. 50°51'32.9\"N 4°30'57.9\"E)"));
+        let mut latitude  = None;
+        let mut longitude = None;
+        let mut parts = dms.split_whitespace();
+        for part in [parts.next(), parts.next()] {
+            let (value, hemisphere) = part.and_then(parse_dms).ok_or_else(invalid)?;
+            let (coordinate, value) = match hemisphere {
+                'N' => (&mut latitude,   value),
+                'S' => (&mut latitude,  -value),
+                'E' => (&mut longitude,  value),
+                _   => (&mut longitude, -value),
+            };
+            if coordinate.replace(value as f32).is_some() {
+                return Err(invalid());
+            }
+        }
+        match (longitude, latitude, parts.next()) {
+            (Some(longitude), Some(latitude), None) => Ok(Self::new(longitude, latitude)),
+            _ => Err(invalid()),
+        }
+    }
+}
+/// Formats one coordinate as degrees, minutes, seconds and hemisphere
+fn dms(value: f32, positive: char, negative: char) -> String {
+    let hemisphere = if value < 0.0 { negative } else { positive };
+    // working with tenths of seconds keeps the rounding from producing 60 seconds
+    let tenths = (f64::from(value).abs() * 36_000.0).round() as u64;
+    let (degrees, minutes, seconds) = (tenths / 36_000, tenths % 36_000 / 600, tenths % 600);
+    format!("{degrees}°{minutes}'{}.{}\"{hemisphere}", seconds / 10, seconds % 10)
+}
+/// Parses one coordinate formatted as degrees, minutes, seconds and hemisphere.
+/// The value is returned along with the (uppercase) hemisphere letter.
+fn parse_dms(dms: &str) -> Option<(f64, char)> {
+    let hemisphere = dms.chars().last()?.to_ascii_uppercase();
+    if !"NSEW".contains(hemisphere) {
+        return None;
+    }
+    let mut value = 0.0;
+    let mut unit  = 1.0;
+    for component in dms[..dms.len() - 1].split_terminator(['°', '\'', '"']) {
+        let component = component.parse::<f64>().ok().filter(|c| *c >= 0.0)?;
+        if unit < 1.0 / 3600.0 {
+            return None;
+        }
+        value += component * unit;
+        unit  /= 60.0;
+    }
+    (unit < 1.0).then_some((value, hemisphere))
+}
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},{}", Decimal(self.longitude), Decimal(self.latitude))
+    }
+}
+/// The location reached after the given fraction (between 0 and 1) of the great
+/// circle going from `from` to `to` (spherical linear interpolation)
+fn great_circle_point(from: Location, to: Location, fraction: f64) -> Location {
+    let (lat1, lon1) = (f64::from(from.latitude).to_radians(), f64::from(from.longitude).to_radians());
+    let (lat2, lon2) = (f64::from(to.latitude).to_radians(),   f64::from(to.longitude).to_radians());
+    let delta = f64::from(from.haversine_distance(&to)) / MEAN_EARTH_RADIUS;
+    if delta.sin().abs() < f64::EPSILON {
+        return from;
+    }
+    let a = ((1.0 - fraction) * delta).sin() / delta.sin();
+    let b = (fraction * delta).sin() / delta.sin();
+    let x = a * lat1.cos() * lon1.cos() + b * lat2.cos() * lon2.cos();
+    let y = a * lat1.cos() * lon1.sin() + b * lat2.cos() * lon2.sin();
+    let z = a * lat1.sin() + b * lat2.sin();
+    let latitude  = z.atan2((x * x + y * y).sqrt());
+    let longitude = y.atan2(x);
+    Location::new(longitude.to_degrees() as f32, latitude.to_degrees() as f32)
+}
+/// The maximum distance (in meters) a location may be moved by its polyline 
+/// encoding in `Coordinates::to_best_polyline`
+pub const POLYLINE_TOLERANCE: f32 = 1.0;
+
+/// The shortest polyline encoding of the given locations which moves none of 
+/// them by more than `tolerance` meters
+fn best_polyline(locations: &[Location], tolerance: f32) -> Coordinates {
+    let polyline6 = encode_polyline(locations, 6);
+    let polyline5 = encode_polyline(locations, 5);
+    let accurate  = decode_polyline(&polyline5, 5).is_ok_and(|decoded| 
+        decoded.iter().zip(locations).all(|(a, b)| a.haversine_distance(b) <= tolerance));
+
+    if accurate && polyline5.len() <= polyline6.len() {
+        Coordinates::Polyline(polyline5)
+    } else {
+        Coordinates::Polyline6(polyline6)
+    }
+}
+
+/// The mean radius of the earth (in meters) as used by the haversine formula
+const MEAN_EARTH_RADIUS: f64 = 6_371_008.8;
+/// The radius of the earth (in meters) as used by the Web Mercator projection
+const EARTH_RADIUS: f64 = 6_378_137.0;
+/// The size (in pixels) of the side of a map tile
+const TILE_SIZE: f64 = 256.0;
+
+/// The one coordinate of a request to a service which, unlike the others, 
+/// accepts no more than a single coordinate (i.e. the nearest service).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SingleCoordinate(pub Location);
+impl SingleCoordinate {
+    /// The location of the coordinate
+    pub fn location(&self) -> Location {
+        self.0
+    }
+}
+impl From<Location> for SingleCoordinate {
+    fn from(location: Location) -> Self {
+        Self(location)
+    }
+}
+impl std::fmt::Display for SingleCoordinate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
 }
 
 /// Most services are quite flexible wrt the coordinates they accept:
@@ -83,13 +312,206 @@ impl std::fmt::Display for Coordinates {
         }
     }
 }
+impl Coordinates {
+    /// The number of coordinates (polylines are decoded to be counted)
+    pub fn count(&self) -> Result<usize, Error> {
+        match self {
+            Self::Single(_)           => Ok(1),
+            Self::Multi(locations)    => Ok(locations.len()),
+            Self::Polyline(polyline)  => Ok(decode_polyline(polyline, 5)?.len()),
+            Self::Polyline6(polyline) => Ok(decode_polyline(polyline, 6)?.len()),
+        }
+    }
+    /// Decodes a polyline (precision 5 for `polyline` or 6 for `polyline6`) into
+    /// an explicit sequence of locations. This is useful when you hold an encoded 
+    /// string but need to send explicit coordinates (e.g. to then apply per-point
+    /// bearings which cannot be carried along with polyline inputs).
+    pub fn from_polyline(polyline: &str, precision: u32) -> Result<Coordinates, Error> {
+        Ok(Self::Multi(decode_polyline(polyline, precision)?))
+    }
+    /// Encodes the coordinates as a polyline of the given precision (5 for
+    /// `polyline` or 6 for `polyline6`). This is the inverse of `from_polyline`.
+    pub fn to_polyline(&self, precision: u32) -> Result<Coordinates, Error> {
+        let locations = match self {
+            Self::Single(location)    => vec![*location],
+            Self::Multi(locations)    => locations.clone(),
+            Self::Polyline(polyline)  => decode_polyline(polyline, 5)?,
+            Self::Polyline6(polyline) => decode_polyline(polyline, 6)?,
+        };
+        Self::from_locations_as_polyline(&locations, precision)
+    }
+    /// Encodes the given locations as a polyline: `Polyline` when the precision is 5
+    /// and `Polyline6` when it is 6. This shortens the urls of the requests bearing
+    /// on many coordinates. Fails with a `PolylineError` for any other precision.
+    pub fn from_locations_as_polyline(locations: &[Location], precision: u32) -> Result<Coordinates, Error> {
+        match precision {
+            5 => Ok(Self::Polyline(encode_polyline(locations, 5))),
+            6 => Ok(Self::Polyline6(encode_polyline(locations, 6))),
+            _ => Err(Error::PolylineError(format!("unsupported precision {precision}")))
+        }
+    }
+    /// Encodes a sequence of coordinates as the shortest polyline which preserves
+    /// every location within `POLYLINE_TOLERANCE` meters. The heuristic goes as 
+    /// follows: the coordinates are encoded with precision 5 and decoded back; if 
+    /// this round trip moved any location by more than the tolerance, precision 
+    /// 6 is used instead. When both are accurate enough, the shorter string wins.
+    /// 
+    /// With a tolerance of 1 meter, precision 5 (about 1.1m between two encodable
+    /// values at the equator) is accurate enough for most traces: this method
+    /// hence mostly shortens urls without hurting the quality of the snapping.
+    /// Single coordinates and polylines are left untouched.
+    pub fn to_best_polyline(&self) -> Coordinates {
+        match self {
+            Self::Multi(locations) => best_polyline(locations, POLYLINE_TOLERANCE),
+            _ => self.clone(),
+        }
+    }
+    /// A synthetic trace going from `from` to `to` along the great circle, with
+    /// one location every `step_meters` (the last step is shorter unless the 
+    /// distance between the endpoints is a multiple of the step). Both endpoints
+    /// are always part of the trace. This is typically used to test and demo 
+    /// the match service. A non positive step yields the endpoints only.
+    pub fn interpolate(from: Location, to: Location, step_meters: f32) -> Coordinates {
+        let distance = f64::from(from.haversine_distance(&to));
+        if step_meters <= 0.0 || distance <= 0.0 {
+            return Coordinates::Multi(vec![from, to]);
+        }
+        let steps = (distance / f64::from(step_meters)).ceil() as usize;
+        let mut locations = (0..steps)
+            .map(|i| great_circle_point(from, to, f64::from(step_meters) * i as f64 / distance))
+            .collect::<Vec<_>>();
+        locations.push(to);
+        Coordinates::Multi(locations)
+    }
+    /// Removes the consecutive duplicate locations (that is, locations closer 
+    /// than `DEDUP_EPSILON` degrees from the one that precedes them) from a 
+    /// sequence of coordinates. This typically cleans up GPS traces that report 
+    /// the same fix repeatedly. Single coordinates and polylines are left untouched.
+    /// 
+    /// Beware: the indices of the waypoints in the response refer to the 
+    /// deduplicated coordinates. Use `dedup_consecutive_with_mapping` if you need 
+    /// to relate these with your original input. Also remember that all the per
+    /// coordinate options (bearings, radiuses, hints, approaches,...) must match
+    /// the deduplicated coordinates.
+    pub fn dedup_consecutive(self) -> Coordinates {
+        self.dedup_consecutive_with_mapping().0
+    }
+    /// Same as `dedup_consecutive` but also returns a mapping such that `mapping[i]` 
+    /// is the index in the deduplicated coordinates of the i-th original location. 
+    /// The mapping is empty for polylines as these are left untouched.
+    pub fn dedup_consecutive_with_mapping(self) -> (Coordinates, Vec<usize>) {
+        match self {
+            Self::Multi(locations) => {
+                let mut mapping = Vec::with_capacity(locations.len());
+                let mut dedup: Vec<Location> = Vec::with_capacity(locations.len());
+                for location in locations {
+                    let duplicate = dedup.last().is_some_and(|last| 
+                        (last.longitude - location.longitude).abs() <= DEDUP_EPSILON &&
+                        (last.latitude  - location.latitude ).abs() <= DEDUP_EPSILON);
+                    if !duplicate {
+                        dedup.push(location);
+                    }
+                    mapping.push(dedup.len() - 1);
+                }
+                (Self::Multi(dedup), mapping)
+            },
+            Self::Single(location) => (Self::Single(location), vec![0]),
+            polyline => (polyline, vec![])
+        }
+    }
+}
+/// Two consecutive locations whose coordinates differ by no more than this many 
+/// degrees (roughly 10 centimeters) are considered duplicates
+pub const DEDUP_EPSILON: f32 = 1e-6;
+
+/// Encodes the given locations following the Google polyline algorithm with
+/// the given precision (5 for `polyline` and 6 for `polyline6`). The locations
+/// are rounded to that many decimals. See also `Coordinates::from_locations_as_polyline`.
+pub fn encode_polyline(locations: &[Location], precision: u32) -> String {
+    let factor = 10_f64.powi(precision as i32);
+    let mut out = String::new();
+    let (mut prev_lat, mut prev_lon) = (0_i64, 0_i64);
+    for location in locations {
+        let lat = (f64::from(location.latitude) * factor).round() as i64;
+        let lon = (f64::from(location.longitude) * factor).round() as i64;
+        encode_polyline_value(lat - prev_lat, &mut out);
+        encode_polyline_value(lon - prev_lon, &mut out);
+        (prev_lat, prev_lon) = (lat, lon);
+    }
+    out
+}
+/// Appends one (delta) value encoded in the polyline format to the output
+fn encode_polyline_value(value: i64, out: &mut String) {
+    let mut value = if value < 0 { !(value << 1) } else { value << 1 };
+    while value >= 0x20 {
+        out.push(char::from((0x20 | (value & 0x1f)) as u8 + 63));
+        value >>= 5;
+    }
+    out.push(char::from(value as u8 + 63));
+}
+
+/// Decodes a string encoded following the Google polyline algorithm with the 
+/// given precision (5 for `polyline` and 6 for `polyline6`)
+pub(crate) fn decode_polyline(encoded: &str, precision: u32) -> Result<Vec<Location>, Error> {
+    let factor = 10_f64.powi(precision as i32);
+    let mut bytes = encoded.bytes();
+    let mut out = vec![];
+    let (mut lat, mut lon) = (0_i64, 0_i64);
+    while let Some(dlat) = decode_polyline_value(&mut bytes)? {
+        let dlon = decode_polyline_value(&mut bytes)?
+            .ok_or_else(|| Error::PolylineError("missing longitude".to_string()))?;
+        lat += dlat;
+        lon += dlon;
+        out.push(Location::new((lon as f64 / factor) as f32, (lat as f64 / factor) as f32));
+    }
+    Ok(out)
+}
+/// Reads one (delta) value from a polyline. Returns None when the input is exhausted
+fn decode_polyline_value(bytes: &mut impl Iterator<Item = u8>) -> Result<Option<i64>, Error> {
+    let mut value = 0_i64;
+    let mut shift = 0;
+    loop {
+        let byte = match bytes.next() {
+            None if shift == 0 => return Ok(None),
+            None => return Err(Error::PolylineError("truncated value".to_string())),
+            Some(byte) => byte,
+        };
+        if !(63..=126).contains(&byte) || shift > 60 {
+            return Err(Error::PolylineError(format!("unexpected character '{}'", char::from(byte))));
+        }
+        let chunk = i64::from(byte - 63);
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk < 0x20 {
+            break;
+        }
+    }
+    Ok(Some(if value & 1 != 0 { !(value >> 1) } else { value >> 1 }))
+}
 
 /// Hint from previous request to derive position in street network (base64 encoded)
-#[derive(Debug, Display, Clone, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[display("{0}")]
 pub struct Hint(String);
+impl Hint {
+    /// A blank hint. It leaves an empty slot in the list of hints, so that the
+    /// coordinate at that position is snapped from scratch.
+    pub fn empty() -> Self {
+        Self(String::new())
+    }
+    /// True iff this hint is blank
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
 
 /// Object used to describe waypoint on a route
+/// 
+/// Two waypoints are equal (and hash alike) iff they have the same `hint`, 
+/// since the hint uniquely identifies the segment a coordinate was snapped to.
+/// This makes it possible to build sets of unique snapped points, but beware
+/// that the hints are only meaningful when they were generated (which is the
+/// default): blank hints make all waypoints equal.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Waypoint {
     /// Name of the street the coordinate snapped to
@@ -106,6 +528,25 @@ pub struct Waypoint {
     /// Array of OpenStreetMap node ids
     pub nodes: Option<Vec<usize>>,
 }
+impl Waypoint {
+    /// Returns true iff the input coordinate was snapped within `max_meters` of 
+    /// the snapped location. A large snapping distance is often the sign of a bad
+    /// input coordinate.
+    pub fn is_well_snapped(&self, max_meters: f32) -> bool {
+        self.distance <= max_meters
+    }
+}
+impl PartialEq for Waypoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.hint == other.hint
+    }
+}
+impl Eq for Waypoint {}
+impl std::hash::Hash for Waypoint {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.hint.hash(state);
+    }
+}
 
 /// An intersection gives a full representation of any cross-way the path passes bay. 
 /// For every step, the very first intersection (intersections[0]) corresponds to the 
@@ -121,6 +562,7 @@ pub struct Intersection {
     pub bearings: Vec<u16>,
     /// An array of strings signifying the classes (as specified in the profile) of the road
     /// exiting the intersection
+    #[serde(default)]
     pub classes:  Vec<String>,
     /// A list of entry flags, corresponding in a 1:1 relationship to the bearings. A value 
     /// of true indicates that the respective road could be entered on a valid route. 
@@ -141,8 +583,9 @@ pub struct Intersection {
     pub out_index: usize,  // TODO: Option<> ?
     /// Array of Lane objects that denote the available turn lanes at the intersection. 
     /// If no lane information is available for an intersection, the lanes property will not 
-    /// be present.
-    pub lanes: Vec<Lane>  // TODO: Option<> ?
+    /// be present (the vector is then empty).
+    #[serde(default)]
+    pub lanes: Vec<Lane>
 }
 
 /// A Lane represents a turn lane at the corresponding turn location.
@@ -163,14 +606,33 @@ pub struct Lane {
     /// * slight left    -> An indication indicating a slight left turn (i.e. slightly bend arrow).
     /// * left           -> An indication indicating a left turn (i.e. bend arrow).
     /// * sharp left     -> An indication indicating a sharp left turn (i.e. strongly bend arrow).
-    indications: Vec<DirectionChange>,
+    pub indications: Vec<DirectionChange>,
     /// a boolean flag indicating whether the lane is a valid choice in the current maneuver
-    valid: bool,
+    pub valid: bool,
+}
+
+/// The guidance about one of the lanes available when performing a maneuver,
+/// as rendered in lane diagrams by navigation UIs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LaneGuidance {
+    /// The indications (e.g. arrows marked on the road) of the lane
+    pub indications: Vec<DirectionChange>,
+    /// Whether the lane is a valid choice for the maneuver
+    pub valid: bool,
+}
+impl From<&Lane> for LaneGuidance {
+    fn from(lane: &Lane) -> Self {
+        Self { indications: lane.indications.clone(), valid: lane.valid }
+    }
 }
 
 /// An indication of a change of direction
-#[derive(Debug, Display, Clone, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DirectionChange {
+    /// No dedicated indication is shown.
+    #[display("none")]
+    #[serde(rename="none")]
+    None,
     /// An indication signaling the possibility to reverse (i.e. fully bend arrow).
     #[display("uturn")]
     #[serde(rename="uturn")]
@@ -203,12 +665,17 @@ pub enum DirectionChange {
     #[display("sharp left")]
     #[serde(rename="sharp left")]
     SharpLeft,
+    /// A direction change this client does not know about (it carries the
+    /// identifier sent by OSRM)
+    #[display("{0}")]
+    #[serde(untagged)]
+    Unknown(String),
 }
 
 /// type A string indicating the type of maneuver. new identifiers might be introduced 
 /// without API change Types unknown to the client should be handled like the turn type, 
 /// the existence of correct modifier values is guranteed
-#[derive(Debug, Display, Clone, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ManeuverType {
     /// a basic turn into direction of the modifier
     #[display("turn")]
@@ -293,6 +760,11 @@ pub enum ManeuverType {
     #[display("exit rotary")]
     #[serde(rename="exit rotary")]
     ExitRotary,
+    /// A type of maneuver this client does not know about (it carries the 
+    /// identifier sent by OSRM). It should be handled like a `Turn`
+    #[display("{0}")]
+    #[serde(untagged)]
+    Unknown(String),
 }
 
 /// A maneuver that must be performed to follow a route
@@ -321,22 +793,124 @@ pub struct StepManeuver {
     /// ## Examples
     /// * turn -> modifier indicates the change in direction accomplished by the turn
     /// * depart / arrive -> modifier indicates the position of departure and arrival 
-    ///           point in relation to the current direction of travel.
+    ///   point in relation to the current direction of travel.
     pub modifier: Option<DirectionChange>,
     /// An optional integer indicating number of the exit to take. The property exists 
     /// for the roundabout / rotary property: Number of the roundabout exit to take. 
     /// If exit is undefined the destination is on the roundabout
     pub exit: Option<u8>,
 }
+impl StepManeuver {
+    /// True iff this is the first maneuver of a leg
+    pub fn is_depart(&self) -> bool {
+        self.maneuver_type == ManeuverType::Depart
+    }
+    /// True iff this is the last maneuver of a leg
+    pub fn is_arrive(&self) -> bool {
+        self.maneuver_type == ManeuverType::Arrive
+    }
+}
+
+/// A distance, in meters. It (de)serializes as a bare number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Meters(pub f32);
+impl Meters {
+    /// The distance in meters
+    pub fn meters(self) -> f32 {
+        self.0
+    }
+    /// The distance in kilometers
+    pub fn kilometers(self) -> f32 {
+        self.0 / 1_000.0
+    }
+    /// The distance in (international) miles
+    pub fn miles(self) -> f32 {
+        self.0 / METERS_PER_MILE
+    }
+}
+impl std::fmt::Display for Meters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} m", self.0)
+    }
+}
+/// The number of meters in one international mile
+const METERS_PER_MILE: f32 = 1_609.344;
+
+/// A duration, in seconds. It (de)serializes as a bare number.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Seconds(pub f32);
+impl Seconds {
+    /// The duration in seconds
+    pub fn seconds(self) -> f32 {
+        self.0
+    }
+    /// The duration in minutes
+    pub fn minutes(self) -> f32 {
+        self.0 / 60.0
+    }
+    /// The duration in hours
+    pub fn hours(self) -> f32 {
+        self.0 / 3_600.0
+    }
+}
+impl std::fmt::Display for Seconds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} s", self.0)
+    }
+}
+
+/// Implements the arithmetic which makes sense for a quantity of the given unit:
+/// quantities add up, scale by a factor, and the ratio of two quantities is a
+/// bare number.
+macro_rules! unit_arithmetic {
+    ($unit:ident) => {
+        impl std::ops::Add for $unit {
+            type Output = $unit;
+            fn add(self, rhs: $unit) -> $unit { $unit(self.0 + rhs.0) }
+        }
+        impl std::ops::AddAssign for $unit {
+            fn add_assign(&mut self, rhs: $unit) { self.0 += rhs.0 }
+        }
+        impl std::ops::Sub for $unit {
+            type Output = $unit;
+            fn sub(self, rhs: $unit) -> $unit { $unit(self.0 - rhs.0) }
+        }
+        impl std::ops::Mul<f32> for $unit {
+            type Output = $unit;
+            fn mul(self, rhs: f32) -> $unit { $unit(self.0 * rhs) }
+        }
+        impl std::ops::Div<f32> for $unit {
+            type Output = $unit;
+            fn div(self, rhs: f32) -> $unit { $unit(self.0 / rhs) }
+        }
+        impl std::ops::Div for $unit {
+            type Output = f32;
+            fn div(self, rhs: $unit) -> f32 { self.0 / rhs.0 }
+        }
+        impl std::iter::Sum for $unit {
+            fn sum<I: Iterator<Item = $unit>>(iter: I) -> $unit { $unit(iter.map(|x| x.0).sum()) }
+        }
+        impl <'a> std::iter::Sum<&'a $unit> for $unit {
+            fn sum<I: Iterator<Item = &'a $unit>>(iter: I) -> $unit { iter.copied().sum() }
+        }
+        impl From<f32> for $unit {
+            fn from(value: f32) -> $unit { $unit(value) }
+        }
+    };
+}
+unit_arithmetic!(Meters);
+unit_arithmetic!(Seconds);
 
 /// A step consists of a maneuver such as a turn or merge, followed by a distance of 
 /// travel along a single way to the subsequent step.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteStep { 
     /// The distance of travel from the maneuver to the subsequent step, in meters.
-    pub distance: f32,
+    pub distance: Meters,
     /// The estimated travel time, in seconds
-    pub duration: f32,
+    pub duration: Seconds,
     /// The unsimplified geometry of the route segment, depending on the geometries parameter.
     pub geometry: Geometry,
     /// The calculated weight of the step.
@@ -350,11 +924,13 @@ pub struct RouteStep {
     /// A string containing an IPA phonetic transcription indicating how to pronounce the name in 
     /// the name property. This property is omitted if pronunciation data is unavailable for the step.
     pub pronunciation: Option<String>,
-    // /// The destinations of the way. Will be undefined if there are no destinations
-    // pub destinations: Vec< WHAT_SHOULD_I_USE_HERE >,
-    // /// The exit numbers or names of the way. Will be undefined if there are no exit numbers or names
-    // pub exits: Vec< WHAT_SHOULD_I_USE_HERE >,
-    
+    /// The destinations of the way, as shown on the signposts (e.g. "A1: Paris").
+    /// Will be undefined if there are no destinations
+    #[serde(default)]
+    pub destinations: Option<String>,
+    /// The exit numbers or names of the way. Will be undefined if there are no exit numbers or names
+    #[serde(default)]
+    pub exits: Option<String>,
     /// A string signifying the mode of transportation
     pub mode: TransportationMode,
     /// A StepManeuver object representing the maneuver
@@ -370,6 +946,66 @@ pub struct RouteStep {
     /// The legal driving side at the location for this step. Either left or right
     pub driving_side: Option<DrivingSide>,
 }
+impl RouteStep {
+    /// The lanes available at the location of the maneuver (that is, at the 
+    /// first intersection of the step), left to right. None when no lane 
+    /// information is available for that intersection.
+    pub fn lane_guidance(&self) -> Option<Vec<LaneGuidance>> {
+        let intersection = self.intersections.first()?;
+        if intersection.lanes.is_empty() {
+            None
+        } else {
+            Some(intersection.lanes.iter().map(LaneGuidance::from).collect())
+        }
+    }
+    /// The name to be spoken by a text-to-speech engine: the IPA pronunciation
+    /// of the way when it is available, its plain name otherwise.
+    pub fn spoken_name(&self) -> &str {
+        self.pronunciation.as_deref().unwrap_or(&self.name)
+    }
+    /// The rotary name to be spoken by a text-to-speech engine: the IPA pronunciation
+    /// of the rotary when it is available, its plain name otherwise. None when
+    /// the step is not a named rotary.
+    pub fn spoken_rotary_name(&self) -> Option<&str> {
+        self.rotary_pronunciation.as_deref().or(self.rotary_name.as_deref())
+    }
+    /// Decodes the geometry of this step into the sequence of locations it 
+    /// comprises, whatever its format: `geometries` is the format the geometries
+    /// were requested in (see `Geometry::decode_as`).
+    pub fn geometry_points(&self, geometries: Geometries) -> Result<Vec<Location>, Error> {
+        self.geometry.decode_as(geometries)
+    }
+    /// The label under which navigation UIs present the road of this step: its
+    /// reference and name separated by a slash (e.g. `A4 / Brussels Ring`). 
+    /// When either of them is missing or empty, the other one is used alone 
+    /// (hence the label is empty when both are).
+    pub fn road_label(&self) -> String {
+        let reference = self.reference.as_deref().unwrap_or_default().trim();
+        let name      = self.name.trim();
+        match (reference.is_empty(), name.is_empty()) {
+            (false, false) => format!("{reference} / {name}"),
+            (false, true)  => reference.to_string(),
+            _              => name.to_string(),
+        }
+    }
+    /// Whether the distance and duration of this step are plausible together.
+    /// A step covering more than `INCONSISTENT_STEP_DISTANCE` meters in no time
+    /// at all, or lasting more than `INCONSISTENT_STEP_DURATION` seconds without
+    /// covering any distance is deemed inconsistent: this typically stems from a
+    /// quirk in the map data (e.g. a missing speed on some way). Steps with 
+    /// neither distance nor duration (like the `arrive` step) are consistent, 
+    /// and so are the quantities below the thresholds (as OSRM rounds them).
+    pub fn is_consistent(&self) -> bool {
+        let moves_instantly = self.duration <= Seconds(0.0) && self.distance > INCONSISTENT_STEP_DISTANCE;
+        let lasts_in_place  = self.distance <= Meters(0.0) && self.duration > INCONSISTENT_STEP_DURATION;
+        !(moves_instantly || lasts_in_place)
+    }
+}
+
+/// The distance (in meters) a step may cover in no time before `RouteStep::is_consistent` flags it
+pub const INCONSISTENT_STEP_DISTANCE: Meters = Meters(1.0);
+/// The duration (in seconds) a step may last in place before `RouteStep::is_consistent` flags it
+pub const INCONSISTENT_STEP_DURATION: Seconds = Seconds(1.0);
 
 /// The legal driving side at a location
 #[derive(Debug, Display, Clone, Serialize, Deserialize)]
@@ -384,29 +1020,38 @@ pub enum DrivingSide {
 }
 
 /// Annotation of the whole route leg with fine-grained information about each segment or node id.
+/// 
+/// Only the requested annotations are returned by OSRM: the arrays that were
+/// not requested are left empty.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Annotation {
     /// The distance, in metres, between each pair of coordinates
-    pub distance: Vec<f32>, // TODO: Option<> ?
+    #[serde(default)]
+    pub distance: Vec<Meters>,
     /// The duration between each pair of coordinates, in seconds. Does not include the 
     /// duration of any turns
-    pub duration: Vec<f32>, // TODO: Option<> ?
+    #[serde(default)]
+    pub duration: Vec<Seconds>,
     /// The index of the datasource for the speed between each pair of coordinates. 0 is the default 
     /// profile, other values are supplied via --segment-speed-file to osrm-contract or osrm-customize. 
     /// String-like names are in the metadata.datasource_names array.
-    pub datasources: Vec<usize>, // TODO: Option<> ?
+    #[serde(default)]
+    pub datasources: Vec<usize>,
     /// The OSM node ID for each coordinate along the route, excluding the first/last user-supplied
     /// coordinates
     pub nodes: Option<Vec<usize>>,
     /// The weights between each pair of coordinates. Does not include any turn costs
-    pub weight: Vec<f32>, // TODO: Option<> ?
+    #[serde(default)]
+    pub weight: Vec<f32>,
     /// Convenience field, calculation of distance / duration rounded to one decimal place
-    pub speed: Vec<f32>, // TODO: Option<> ?
+    #[serde(default)]
+    pub speed: Vec<f32>,
     /// Metadata related to other annotations
-    pub metadata: AnnotationMetaData, // TODO: Option<> ?
+    #[serde(default)]
+    pub metadata: AnnotationMetaData,
 }
 /// Some meta-data attached to route annotations
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct AnnotationMetaData {
     /// The names of the datasources used for the speed between each pair of coordinates. lua profile 
     /// is the default profile, other values arethe filenames supplied via --segment-speed-file to 
@@ -417,9 +1062,9 @@ pub struct AnnotationMetaData {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteLeg {
     /// The distance traveled by this route leg, in float meters.
-    pub distance: f32,
+    pub distance: Meters,
     /// The estimated travel time, in of seconds
-    pub duration: f32,
+    pub duration: Seconds,
     /// The calculated weight of the route leg.
     pub weight: f32,
     /// Summary of the route taken as string. Depends on the summary parameter
@@ -435,13 +1080,133 @@ pub struct RouteLeg {
     /// * false-> undefined (none)
     pub annotation: Option<Annotation>
 }
+impl RouteLeg {
+    /// Pairs each annotated speed (see `RouteAnnotationRequest::Speed`) with the 
+    /// location where its segment starts, which yields a plottable speed profile 
+    /// along the leg. The leg geometry is rebuilt from the geometries of its steps
+    /// (`geometries` is the format they were requested in): hence this profile 
+    /// is empty unless both `steps` and the speed annotation were requested.
+    /// 
+    /// This fails with `MisalignedAnnotation` when there is not exactly one 
+    /// speed per segment of the leg.
+    pub fn speed_profile(&self, geometries: Geometries) -> Result<Vec<(Location, f32)>, Error> {
+        let speeds = match &self.annotation {
+            Some(annotation) if !annotation.speed.is_empty() && !self.steps.is_empty() => &annotation.speed,
+            _ => return Ok(vec![])
+        };
+        let coordinates = self.coordinates(geometries)?;
+        let expected = coordinates.len().saturating_sub(1);
+        if speeds.len() != expected {
+            return Err(Error::MisalignedAnnotation { annotation: "speed", expected, actual: speeds.len() });
+        }
+        let profile = coordinates.into_iter()
+            .zip(speeds.iter().copied())
+            .collect();
+        Ok(profile)
+    }
+    /// The segments of the leg (between each pair of consecutive coordinates of 
+    /// its geometry) along with their annotations. The leg geometry is rebuilt
+    /// from the geometries of its steps (`geometries` is the format they were 
+    /// requested in): hence the steps must have been requested along with the 
+    /// annotations. The annotations which were not requested are None in all 
+    /// segments.
+    /// 
+    /// This fails with `MissingData` when the steps or annotations are missing,
+    /// and with `MisalignedAnnotation` when an annotation array does not have 
+    /// exactly one value per segment.
+    pub fn annotated_segments(&self, geometries: Geometries) -> Result<Vec<AnnotatedSegment>, Error> {
+        let annotation = self.annotation.as_ref().ok_or(Error::MissingData("annotation"))?;
+        if self.steps.is_empty() {
+            return Err(Error::MissingData("steps"));
+        }
+        let coordinates = self.coordinates(geometries)?;
+        let expected = coordinates.len().saturating_sub(1);
+        fn aligned<T: Copy>(name: &'static str, values: &[T], expected: usize) -> Result<Option<Vec<T>>, Error> {
+            match values.len() {
+            0 => Ok(None),
+            n if n == expected => Ok(Some(values.to_vec())),
+                n => Err(Error::MisalignedAnnotation { annotation: name, expected, actual: n }),
+            }
+        }
+        let distance = aligned("distance", &annotation.distance, expected)?;
+        let duration = aligned("duration", &annotation.duration, expected)?;
+        let weight   = aligned("weight",   &annotation.weight,   expected)?;
+        let speed    = aligned("speed",    &annotation.speed,    expected)?;
+
+        fn value<T: Copy>(values: &Option<Vec<T>>, i: usize) -> Option<T> {
+            values.as_ref().map(|v| v[i])
+        }
+        let segments = coordinates.windows(2).enumerate()
+            .map(|(i, pair)| AnnotatedSegment {
+                from:     pair[0],
+                to:       pair[1],
+                distance: value(&distance, i),
+                duration: value(&duration, i),
+                weight:   value(&weight, i),
+                speed:    value(&speed, i),
+            })
+            .collect();
+        Ok(segments)
+    }
+    /// The location of the maneuver of each step, in order. This is where the 
+    /// turn-point markers are typically placed on a map.
+    pub fn maneuver_locations(&self) -> Vec<Location> {
+        self.steps.iter().map(|step| step.maneuver.location).collect()
+    }
+    /// The type of the maneuver of each step, in order (parallel to the 
+    /// `maneuver_locations`)
+    pub fn maneuver_types(&self) -> Vec<ManeuverType> {
+        self.steps.iter().map(|step| step.maneuver.maneuver_type.clone()).collect()
+    }
+    /// The first step of the leg, provided it is a depart maneuver (None when
+    /// the steps were not requested)
+    pub fn depart_step(&self) -> Option<&RouteStep> {
+        self.steps.first().filter(|step| step.maneuver.is_depart())
+    }
+    /// The last step of the leg, provided it is an arrive maneuver (None when 
+    /// the steps were not requested)
+    pub fn arrive_step(&self) -> Option<&RouteStep> {
+        self.steps.last().filter(|step| step.maneuver.is_arrive())
+    }
+    /// The coordinates along the leg, as rebuilt from the geometries of its steps
+    /// (requested in the given format)
+    fn coordinates(&self, geometries: Geometries) -> Result<Vec<Location>, Error> {
+        let mut coordinates: Vec<Location> = vec![];
+        for step in self.steps.iter() {
+            for point in step.geometry_points(geometries)? {
+                // consecutive steps share their boundary point
+                if coordinates.last() != Some(&point) {
+                    coordinates.push(point);
+                }
+            }
+        }
+        Ok(coordinates)
+    }
+}
+/// One segment of a leg (between two consecutive coordinates of its geometry)
+/// along with its annotations (see `RouteLeg::annotated_segments`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnnotatedSegment {
+    /// The location where the segment starts
+    pub from: Location,
+    /// The location where the segment ends
+    pub to: Location,
+    /// The distance of the segment (None unless annotated)
+    pub distance: Option<Meters>,
+    /// The duration of the segment (None unless annotated)
+    pub duration: Option<Seconds>,
+    /// The weight of the segment (None unless annotated)
+    pub weight: Option<f32>,
+    /// The speed along the segment, in meters per second (None unless annotated)
+    pub speed: Option<f32>,
+}
 /// Represents a route through (potentially multiple) waypoints.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Route {
     /// The distance traveled by this route, in meters.
-    pub distance: f32,
+    pub distance: Meters,
     /// The estimated travel time, in seconds
-    pub duration: f32,
+    pub duration: Seconds,
     /// The whole geometry of the route value depending on overview parameter, format depending on 
     /// the geometries parameter. See RouteStep's geometry property for a parameter documentation.
     pub geometry: Geometry, // TODO: Option<> ?
@@ -452,6 +1217,289 @@ pub struct Route {
     /// The legs between the given waypoints, an array of RouteLeg objects.
     pub legs: Vec<RouteLeg>,
 }
+impl Route {
+    /// The location reached after the given fraction (between 0 and 1) of the 
+    /// route; either in terms of distance or in terms of travel time. This is 
+    /// typically where UIs place a marker or an ETA label representing the route.
+    /// 
+    /// When measuring fractions of the travel time, the steps of the route are 
+    /// walked so as to find the one during which that time is reached (the speed
+    /// is assumed to be constant along a step). Without steps, the speed is 
+    /// assumed to be constant along the whole route. `geometries` is the format
+    /// in which the geometries were requested.
+    /// 
+    /// Returns None when the geometry is empty or cannot be decoded.
+    pub fn point_at_fraction(&self, fraction: f32, by: FractionBasis, geometries: Geometries) -> Option<Location> {
+        let fraction = fraction.clamp(0.0, 1.0);
+        let steps = self.legs.iter().flat_map(|leg| leg.steps.iter()).collect::<Vec<_>>();
+        let total = steps.iter().map(|step| step.duration).sum::<Seconds>();
+
+        if by == FractionBasis::Distance || total <= Seconds(0.0) {
+            let points = self.geometry.decode_as(geometries).ok()?;
+            return point_along(&points, fraction);
+        }
+
+        let target = total * fraction;
+        let mut elapsed = Seconds(0.0);
+        for step in steps.iter() {
+            if step.duration > Seconds(0.0) && elapsed + step.duration >= target {
+                let points = step.geometry.decode_as(geometries).ok()?;
+                return point_along(&points, (target - elapsed) / step.duration);
+            }
+            elapsed += step.duration;
+        }
+        let points = steps.last()?.geometry.decode_as(geometries).ok()?;
+        points.last().copied()
+    }
+    /// Exports the geometry of the route as a WKT `LINESTRING` which can directly
+    /// be inserted in PostGIS or other spatial databases. `geometries` is the
+    /// format in which the geometry was requested.
+    pub fn to_wkt(&self, geometries: Geometries) -> Result<String, Error> {
+        let points = self.geometry.decode_as(geometries)?
+            .iter()
+            .map(|l| format!("{} {}", l.longitude, l.latitude))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Ok(format!("LINESTRING({points})"))
+    }
+    /// The running totals of the (distance in meters, duration in seconds) along
+    /// the route, at each step boundary: the series starts at (0, 0) and adds one 
+    /// entry per step. The legs for which no step is available contribute one 
+    /// single entry instead (leg-level granularity). The last entry hence gives
+    /// the totals of the route. This is typically what progress bars and ETA 
+    /// displays rely on.
+    pub fn cumulative(&self) -> Vec<(Meters, Seconds)> {
+        let mut series = vec![(Meters(0.0), Seconds(0.0))];
+        let (mut distance, mut duration) = (Meters(0.0), Seconds(0.0));
+        for leg in self.legs.iter() {
+            if leg.steps.is_empty() {
+                distance += leg.distance;
+                duration += leg.duration;
+                series.push((distance, duration));
+            }
+            for step in leg.steps.iter() {
+                distance += step.distance;
+                duration += step.duration;
+                series.push((distance, duration));
+            }
+        }
+        series
+    }
+    /// The ratio of the weight of the route to its duration. With a `duration`
+    /// weight, or when the profile does not penalize any road of the route, 
+    /// this ratio is 1. It departs from 1 when the profile biases the route 
+    /// (e.g. the `routability` weight penalizing some roads). A route lasting 
+    /// no time at all has a ratio of 1.
+    pub fn weight_duration_ratio(&self) -> f32 {
+        if self.duration > Seconds(0.0) {
+            self.weight / self.duration.seconds()
+        } else {
+            1.0
+        }
+    }
+    /// Whether the weight of the route departs from its duration by more than 
+    /// the given relative `threshold` (e.g. 0.1 flags routes whose weight is
+    /// 10% above or below their duration). See `weight_duration_ratio`.
+    pub fn is_biased(&self, threshold: f32) -> bool {
+        (self.weight_duration_ratio() - 1.0).abs() > threshold
+    }
+    /// The ratio of the distance of the route to the straight-line (haversine)
+    /// distance between its first and last waypoints. The closer to 1, the more
+    /// direct the route. As the route does not hold its waypoints, these must
+    /// be given (typically the `waypoints` of the `RouteResponse`).
+    /// 
+    /// Returns None when less than two waypoints are available (e.g. with
+    /// `skip_waypoints`) or when the first and last waypoints coincide.
+    pub fn detour_ratio(&self, waypoints: &[Waypoint]) -> Option<f32> {
+        let (first, last) = (waypoints.first()?, waypoints.last()?);
+        let straight = first.location.haversine_distance(&last.location);
+        if waypoints.len() < 2 || straight <= 0.0 {
+            None
+        } else {
+            Some(self.distance.meters() / straight)
+        }
+    }
+    /// The indices of the steps which are not consistent (see `RouteStep::is_consistent`).
+    /// The steps of all the legs are numbered in sequence, as if the route had
+    /// one single leg. This is meant to surface the anomalies of the map data
+    /// when assessing the quality of the routes.
+    pub fn suspicious_steps(&self) -> Vec<usize> {
+        self.legs.iter()
+            .flat_map(|leg| leg.steps.iter())
+            .enumerate()
+            .filter(|(_, step)| !step.is_consistent())
+            .map(|(i, _)| i)
+            .collect()
+    }
+    /// Simplifies the geometry of the route for display at the given zoom level
+    /// (the resolution OSRM applies with `OverviewRequest::Simplified`, but for
+    /// a zoom level of your choosing). The geometry is projected on the pixels of
+    /// the map at that zoom (see `Location::to_pixel`), then simplified with the
+    /// Douglas–Peucker algorithm using a tolerance of `SIMPLIFICATION_TOLERANCE`
+    /// pixel: the points it drops would not be visible at that zoom anyway. 
+    /// 
+    /// Request the `Full` overview for the simplification to be meaningful.
+    /// `geometries` is the format in which the geometry was requested.
+    pub fn simplify_to_zoom(&self, zoom: u8, geometries: Geometries) -> Result<Vec<Location>, Error> {
+        let points = self.geometry.decode_as(geometries)?;
+        let pixels = points.iter().map(|p| p.to_pixel(zoom)).collect::<Vec<_>>();
+        let keep   = douglas_peucker(&pixels, SIMPLIFICATION_TOLERANCE);
+        Ok(points.into_iter().zip(keep).filter(|(_, keep)| *keep).map(|(p, _)| p).collect())
+    }
+    /// Exports the route as a GPX document comprising one track (`<trk>`) made 
+    /// of the points of its geometry. This is what handheld GPS units and fitness 
+    /// apps import. `geometries` is the format in which the geometry was requested.
+    /// See also `RouteResponse::to_gpx` which includes the waypoints.
+    pub fn to_gpx(&self, geometries: Geometries) -> Result<String, Error> {
+        to_gpx([], &[self], geometries)
+    }
+    /// Exports the route as a GeoJSON `Feature` whose properties are the distance,
+    /// duration, weight and weight name of the route. A geojson geometry is 
+    /// exported as is: the elevation of its points (if any) is preserved as the
+    /// third coordinate (`[lon, lat, ele]`). `geometries` is the format in which
+    /// the geometry was requested.
+    pub fn to_geojson(&self, geometries: Geometries) -> Result<String, Error> {
+        let geometry = match &self.geometry {
+            Geometry::Explicit(geojson) => Cow::Borrowed(geojson),
+            Geometry::Encoded(_) => {
+                let coordinates = self.geometry.decode_as(geometries)?
+                    .into_iter()
+                    .map(GeoJsonPoint::from)
+                    .collect();
+                Cow::Owned(GeoJsonGeometry::LineString { coordinates })
+            }
+        };
+        let feature = GeoJsonFeature {
+            kind: "Feature",
+            geometry,
+            properties: RouteProperties {
+                distance:    self.distance,
+                duration:    self.duration,
+                weight:      self.weight,
+                weight_name: &self.weight_name,
+            }
+        };
+        Ok(serde_json::to_string(&feature)?)
+    }
+    /// A compact summary of the route meant to be persisted (see `RouteSummary`).
+    /// `geometries` is the format in which the geometry was requested: whatever
+    /// that format, the summary holds a polyline of precision 5. The waypoint
+    /// names are those of the roads where the legs start and end: these are 
+    /// only known when the steps were requested (the list is empty otherwise).
+    pub fn summarize(&self, geometries: Geometries) -> Result<RouteSummary, Error> {
+        let points = self.geometry.decode_as(geometries)?;
+        let depart   = self.legs.first().and_then(RouteLeg::depart_step);
+        let arrivals = self.legs.iter().map(RouteLeg::arrive_step).collect::<Option<Vec<_>>>();
+        let waypoint_names = match (depart, arrivals) {
+            (Some(depart), Some(arrivals)) => std::iter::once(depart)
+                .chain(arrivals)
+                .map(|step| step.name.clone())
+                .collect(),
+            _ => vec![],
+        };
+        Ok(RouteSummary {
+            distance: self.distance.meters(),
+            duration: self.duration.seconds(),
+            geometry_polyline: encode_polyline(&points, 5),
+            waypoint_names,
+        })
+    }
+}
+
+/// A compact summary of a route (see `Route::summarize`). Unlike `Route`, the
+/// serialized form of this summary does not follow the evolutions of the OSRM
+/// response format: it is hence suited to the storage of routes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RouteSummary {
+    /// The distance traveled by the route, in meters
+    pub distance: f32,
+    /// The estimated travel time, in seconds
+    pub duration: f32,
+    /// The geometry of the route, encoded as a polyline (precision 5)
+    pub geometry_polyline: String,
+    /// The names of the roads of the waypoints, in order
+    pub waypoint_names: Vec<String>,
+}
+
+/// A GeoJSON feature, as exported by `Route::to_geojson`
+#[derive(Serialize)]
+struct GeoJsonFeature<'a> {
+    #[serde(rename="type")]
+    kind: &'static str,
+    geometry: Cow<'a, GeoJsonGeometry>,
+    properties: RouteProperties<'a>,
+}
+/// The properties of a route exported as a GeoJSON feature
+#[derive(Serialize)]
+struct RouteProperties<'a> {
+    distance: Meters,
+    duration: Seconds,
+    weight: f32,
+    weight_name: &'a str,
+}
+
+/// The tolerance (in pixels) of the simplification in `Route::simplify_to_zoom`
+pub const SIMPLIFICATION_TOLERANCE: f64 = 1.0;
+
+/// Tells which of the points of the polyline are kept by the Douglas–Peucker
+/// simplification with the given tolerance (the end points are always kept)
+pub(crate) fn douglas_peucker(points: &[(f64, f64)], tolerance: f64) -> Vec<bool> {
+    let mut keep = vec![false; points.len()];
+    if let (Some(first), Some(last)) = (keep.first_mut(), points.len().checked_sub(1)) {
+        *first = true;
+        keep[last] = true;
+    }
+    let mut stack = vec![(0, points.len().saturating_sub(1))];
+    while let Some((start, end)) = stack.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| (i, distance_to_segment(points[i], points[start], points[end])))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                stack.push((start, i));
+                stack.push((i, end));
+            }
+        }
+    }
+    keep
+}
+/// The euclidean distance between point `p` and the segment `[a, b]`
+fn distance_to_segment(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let length2  = dx * dx + dy * dy;
+    let t = if length2 == 0.0 { 0.0 } else { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length2).clamp(0.0, 1.0) };
+    let (x, y) = (a.0 + t * dx, a.1 + t * dy);
+    ((p.0 - x).powi(2) + (p.1 - y).powi(2)).sqrt()
+}
+
+/// How fractions of a route are measured (see `Route::point_at_fraction`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FractionBasis {
+    /// Fraction of the distance travelled along the route
+    Distance,
+    /// Fraction of the travel time along the route
+    Duration,
+}
+
+/// The location reached after the given fraction of the length of the polyline
+fn point_along(points: &[Location], fraction: f32) -> Option<Location> {
+    let lengths = points.windows(2)
+        .map(|segment| segment[0].haversine_distance(&segment[1]))
+        .collect::<Vec<_>>();
+    let mut remaining = fraction * lengths.iter().sum::<f32>();
+    for (segment, length) in points.windows(2).zip(lengths) {
+        if length > 0.0 && remaining <= length {
+            let t = remaining / length;
+            let (from, to) = (segment[0], segment[1]);
+            return Some(Location::new(
+                from.longitude + t * (to.longitude - from.longitude),
+                from.latitude  + t * (to.latitude  - from.latitude)));
+        }
+        remaining -= length;
+    }
+    points.last().copied()
+}
 
 /// Represents a geometry which can either be encoded with polyline of polyline6
 /// or explicit in the form of a geojson
@@ -463,6 +1511,39 @@ pub enum Geometry {
     /// When the geometry is explicitly detailed
     Explicit(GeoJsonGeometry)
 }
+impl Geometry {
+    /// Decodes the geometry into the sequence of locations it comprises. Polylines
+    /// are assumed to use the default precision of 5 (`GeometryType::Polyline`);
+    /// use `decode_with_precision` for the geometries requested as `polyline6`.
+    /// GeoJSON geometries are flattened (see `GeoJsonGeometry::all_points`).
+    pub fn decode(&self) -> Result<Vec<Location>, Error> {
+        self.decode_with_precision(5)
+    }
+    /// Decodes the geometry into the sequence of locations it comprises. The given
+    /// precision is used to decode polylines (5 for `polyline`, 6 for `polyline6`).
+    pub fn decode_with_precision(&self, precision: u32) -> Result<Vec<Location>, Error> {
+        match self {
+            Self::Encoded(polyline) => decode_polyline(polyline, precision),
+            Self::Explicit(geojson) => Ok(geojson.all_points()),
+        }
+    }
+    /// Decodes the geometry of a response whose geometries were requested in the
+    /// given format (see the `geometries` option of the requests): polylines are
+    /// decoded with the precision of that format. This is what all the exports
+    /// of the routes rely on. 
+    /// 
+    /// This fails with a `PolylineError` when a polyline is received although
+    /// GeoJSON geometries were requested (its precision is then unknown).
+    pub fn decode_as(&self, geometries: Geometries) -> Result<Vec<Location>, Error> {
+        match (self, geometries) {
+            (Self::Encoded(polyline), Geometries::Polyline)  => decode_polyline(polyline, 5),
+            (Self::Encoded(polyline), Geometries::Polyline6) => decode_polyline(polyline, 6),
+            (Self::Encoded(_), Geometries::GeoJson) => 
+                Err(Error::PolylineError("received a polyline whereas geojson geometries were requested".to_string())),
+            (Self::Explicit(geojson), _) => Ok(geojson.all_points()),
+        }
+    }
+}
 
 /// GeoJSON[1] is an open standard format designed for representing simple geographical features, 
 /// along with their non-spatial attributes. It is based on the JSON format.
@@ -493,6 +1574,31 @@ pub enum GeoJsonGeometry {
     MultiLineString { coordinates: Vec<Vec<GeoJsonPoint>> },
     MultiPolygon { coordinates: Vec<Vec<Vec<GeoJsonPoint>>> },
 }
+impl GeoJsonGeometry {
+    /// Flattens the geometry into the list of all the locations it comprises,
+    /// regardless of how deeply these are nested in the geometry.
+    pub fn all_points(&self) -> Vec<Location> {
+        match self {
+            Self::Point { coordinates }           => vec![coordinates.location()],
+            Self::LineString { coordinates }      => coordinates.iter().map(|p| p.location()).collect(),
+            Self::MultiPoint { coordinates }      => coordinates.iter().map(|p| p.location()).collect(),
+            Self::Polygon { coordinates }         => coordinates.iter().flatten().map(|p| p.location()).collect(),
+            Self::MultiLineString { coordinates } => coordinates.iter().flatten().map(|p| p.location()).collect(),
+            Self::MultiPolygon { coordinates }    => coordinates.iter().flatten().flatten().map(|p| p.location()).collect(),
+        }
+    }
+    /// The total number of points comprised in the geometry
+    pub fn point_count(&self) -> usize {
+        match self {
+            Self::Point { .. }                    => 1,
+            Self::LineString { coordinates }      => coordinates.len(),
+            Self::MultiPoint { coordinates }      => coordinates.len(),
+            Self::Polygon { coordinates }         => coordinates.iter().map(Vec::len).sum(),
+            Self::MultiLineString { coordinates } => coordinates.iter().map(Vec::len).sum(),
+            Self::MultiPolygon { coordinates }    => coordinates.iter().flatten().map(Vec::len).sum(),
+        }
+    }
+}
 
 /// Points are [x, y] or [x, y, z]. They may be [longitude, latitude] or [eastings, northings]. 
 /// Elevation is an optional third number. They are decimal numbers. [6]
@@ -527,4 +1633,750 @@ impl From<Location> for GeoJsonPoint {
     fn from(Location { longitude, latitude }: Location) -> Self {
         Self::Regular([longitude, latitude])
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use super::best_polyline;
+    use crate::{RouteStep, RouteLeg, RouteSummary, AnnotatedSegment, Error, Meters, Seconds, ManeuverType, Waypoint, Route, FractionBasis, Location, Coordinates, GeoJsonGeometry, Geometry, Geometries, TransportationMode, DirectionChange, LaneGuidance, encode_polyline};
+
+    #[test]
+    fn transportation_mode_display_is_as_str() {
+        for mode in TransportationMode::all() {
+            assert_eq!(mode.as_str(), mode.to_string());
+        }
+    }
+    #[test]
+    fn transportation_mode_is_serialized_as_travel_mode() {
+        let modes = TransportationMode::all().map(|mode| serde_json::to_string(&mode).unwrap());
+        assert_eq!([r#""driving""#, r#""cycling""#, r#""walking""#], modes);
+        for mode in TransportationMode::all() {
+            assert_eq!(mode, serde_json::from_str(&format!("{:?}", mode.travel_mode())).unwrap());
+            assert_eq!(mode, serde_json::from_str(&format!("{:?}", mode.as_str())).unwrap());
+        }
+        assert!(serde_json::from_str::<TransportationMode>(r#""flying""#).is_err());
+    }
+
+    fn assert_close(expected: f64, actual: f64, tolerance: f64) {
+        assert!((expected - actual).abs() <= tolerance, "expected {expected}, got {actual}");
+    }
+    #[test]
+    fn haversine_distance_reference_values() {
+        // one degree of latitude
+        assert_close(111_195.0, f64::from(Location::new(0.0, 0.0).haversine_distance(&Location::new(0.0, 1.0))), 1.0);
+        // Paris -> London
+        let paris  = Location::new(2.3522, 48.8566);
+        let london = Location::new(-0.1278, 51.5074);
+        assert_close(343_556.0, f64::from(paris.haversine_distance(&london)), 100.0);
+    }
+
+    #[test]
+    fn web_mercator_reference_values() {
+        let (x, y) = Location::new(0.0, 0.0).to_web_mercator();
+        assert_close(0.0, x, 1e-6);
+        assert_close(0.0, y, 1e-6);
+
+        let (x, y) = Location::new(180.0, 85.051_13).to_web_mercator();
+        assert_close(20_037_508.34, x, 0.01);
+        assert_close(20_037_508.34, y, 5.0);
+
+        // Brussels
+        let (x, y) = Location::new(4.35, 50.85).to_web_mercator();
+        assert_close(484_239.78, x, 1.0);
+        assert_close(6_594_803.23, y, 1.0);
+    }
+    #[test]
+    fn web_mercator_round_trip() {
+        let location = Location::new(4.516091, 50.859136);
+        let (x, y) = location.to_web_mercator();
+        let back = Location::from_web_mercator(x, y);
+        assert_close(f64::from(location.longitude), f64::from(back.longitude), 1e-5);
+        assert_close(f64::from(location.latitude),  f64::from(back.latitude),  1e-5);
+    }
+    #[test]
+    fn to_pixel_reference_values() {
+        let (x, y) = Location::new(0.0, 0.0).to_pixel(0);
+        assert_close(128.0, x, 1e-6);
+        assert_close(128.0, y, 1e-6);
+
+        let (x, y) = Location::new(-180.0, 85.051_13).to_pixel(1);
+        assert_close(0.0, x, 1e-6);
+        assert_close(0.0, y, 1e-3);
+    }
+
+    #[test]
+    fn snap_to_grid_merges_nearby_locations() {
+        let a = Location::new(4.516091, 50.859136);
+        let b = Location::new(4.516191, 50.859166);
+        let (sa, sb) = (a.snap_to_grid(100.0), b.snap_to_grid(100.0));
+        assert_eq!(sa, sb);
+        assert!(a.haversine_distance(&sa) <= 100.0 / 2_f32.sqrt());
+        assert_eq!(sa, sa.snap_to_grid(100.0));
+        assert_ne!(sa, a.snap_to_grid(10.0));
+        assert_eq!(a, a.snap_to_grid(0.0));
+    }
+    #[test]
+    fn interpolate_along_the_great_circle() {
+        let (from, to) = (Location::new(4.35, 50.85), Location::new(4.40, 50.80));
+        let distance = from.haversine_distance(&to);
+        let locations = match Coordinates::interpolate(from, to, 1000.0) {
+            Coordinates::Multi(locations) => locations,
+            other => panic!("unexpected {other:?}"),
+        };
+        assert_eq!((distance / 1000.0).ceil() as usize + 1, locations.len());
+        assert_eq!(from, locations[0]);
+        assert_eq!(to, *locations.last().unwrap());
+        for pair in locations[..locations.len() - 1].windows(2) {
+            assert_close(1000.0, f64::from(pair[0].haversine_distance(&pair[1])), 1.0);
+        }
+        assert!(locations[locations.len() - 2].haversine_distance(&to) <= 1000.0);
+    }
+    #[test]
+    fn interpolate_without_step() {
+        let (from, to) = (Location::new(4.35, 50.85), Location::new(4.40, 50.80));
+        assert_eq!("4.35,50.85;4.4,50.8", Coordinates::interpolate(from, to, 0.0).to_string());
+    }
+    #[test]
+    fn to_dms_reference_values() {
+        assert_eq!("50°51'32.9\"N 4°30'57.9\"E", Location::new(4.516091, 50.859136).to_dms());
+        assert_eq!("33°52'7.7\"S 151°12'33.5\"E", Location::new(151.2093, -33.8688).to_dms());
+        assert_eq!("0°0'0.0\"N 74°0'0.0\"W",      Location::new(-73.999999, 0.0).to_dms());
+    }
+    #[test]
+    fn dms_round_trip() {
+        for location in [Location::new(4.516091, 50.859136), Location::new(151.2093, -33.8688), Location::new(-122.4194, 37.7749)] {
+            let back = Location::from_dms(&location.to_dms()).unwrap();
+            assert_close(f64::from(location.longitude), f64::from(back.longitude), 3e-5);
+            assert_close(f64::from(location.latitude),  f64::from(back.latitude),  3e-5);
+        }
+    }
+    #[test]
+    fn from_dms_accepts_partial_and_reordered_input() {
+        let location = Location::from_dms("4°30'E 50.5°N").unwrap();
+        assert_close(4.5,  f64::from(location.longitude), 1e-6);
+        assert_close(50.5, f64::from(location.latitude),  1e-6);
+    }
+    #[test]
+    fn from_dms_rejects_malformed_input() {
+        for dms in ["", "50°51'32.9\"N", "50°N 4°N", "50°51'32.9\"X 4°E", "50°5a'N 4°E", "50°N 4°E 3°E", "1°2'3\"4\"N 4°E"] {
+            assert!(Location::from_dms(dms).is_err(), "{dms}");
+        }
+    }
+
+    #[test]
+    fn coordinates_from_polyline_and_back() {
+        let coords = Coordinates::from_polyline("_p~iF~ps|U_ulLnnqC_mqNvxq`@", 5).unwrap();
+        assert_eq!("-120.2,38.5;-120.95,40.7;-126.453,43.252", coords.to_string());
+
+        let polyline = coords.to_polyline(5).unwrap();
+        assert_eq!("polyline(_p~iF~ps|U_ulLnnqC_mqNvxq`@)", polyline.to_string());
+        let polyline6 = polyline.to_polyline(6).unwrap();
+        assert_eq!("polyline6(_izlhAxrlgdFa{geC~ywl@{jwzCz`{nI)", polyline6.to_string());
+        assert!(coords.to_polyline(7).is_err());
+    }
+    #[test]
+    fn best_polyline_prefers_precision_5_within_tolerance() {
+        let coords = Coordinates::Multi(vec![Location::new(4.516091, 50.859136), Location::new(4.621039, 50.668585)]);
+        assert!(matches!(coords.to_best_polyline(), Coordinates::Polyline(_)));
+        let single = Coordinates::Single(Location::new(4.516091, 50.859136));
+        assert!(matches!(single.to_best_polyline(), Coordinates::Single(_)));
+    }
+    #[test]
+    fn best_polyline_falls_back_to_precision_6_beyond_tolerance() {
+        let locations = vec![Location::new(4.516091, 50.859136), Location::new(4.621039, 50.668585)];
+        assert!(matches!(best_polyline(&locations, 0.1), Coordinates::Polyline6(_)));
+        // coordinates with at most 5 decimals survive the round trip unharmed
+        let locations = vec![Location::new(4.5, 50.8), Location::new(4.6, 50.7)];
+        assert!(matches!(best_polyline(&locations, 0.1), Coordinates::Polyline(_)));
+    }
+    #[test]
+    fn coordinates_from_invalid_polyline() {
+        assert!(Coordinates::from_polyline("_p~iF~ps|U_", 5).is_err());
+        assert!(Coordinates::from_polyline("_p~iF", 5).is_err());
+        assert!(Coordinates::from_polyline("_p~iF ~ps|U", 5).is_err());
+    }
+
+    #[test]
+    fn dedup_consecutive_removes_adjacent_duplicates_only() {
+        let coords = Coordinates::Multi(vec![
+            Location::new(4.5, 50.8),
+            Location::new(4.5, 50.8),
+            Location::new(4.6, 50.7),
+            Location::new(4.5, 50.8),
+            Location::new(4.5, 50.8),
+        ]);
+        let (dedup, mapping) = coords.dedup_consecutive_with_mapping();
+        assert_eq!("4.5,50.8;4.6,50.7;4.5,50.8", dedup.to_string());
+        assert_eq!(vec![0, 0, 1, 2, 2], mapping);
+    }
+    #[test]
+    fn dedup_consecutive_leaves_single_and_polylines_untouched() {
+        let single = Coordinates::Single(Location::new(4.5, 50.8)).dedup_consecutive_with_mapping();
+        assert_eq!("4.5,50.8", single.0.to_string());
+        assert_eq!(vec![0], single.1);
+        let polyline = Coordinates::Polyline("_p~iF~ps|U".to_string()).dedup_consecutive();
+        assert_eq!("polyline(_p~iF~ps|U)", polyline.to_string());
+    }
+
+    #[test]
+    fn encode_polyline_matches_reference_implementation() {
+        let locations = [
+            Location::new(-120.2,   38.5),
+            Location::new(-120.95,  40.7),
+            Location::new(-126.453, 43.252),
+        ];
+        assert_eq!("_p~iF~ps|U_ulLnnqC_mqNvxq`@", encode_polyline(&locations, 5));
+    }
+
+    #[test]
+    fn locations_round_trip_through_polylines() {
+        let locations = vec![Location::new(4.351712, 50.846557), Location::new(4.402771, 50.79821), Location::new(-0.12776, 51.50735)];
+        for (precision, tolerance) in [(5, 1.2), (6, 0.12)] {
+            let coordinates = Coordinates::from_locations_as_polyline(&locations, precision).unwrap();
+            let decoded = match (precision, &coordinates) {
+                (5, Coordinates::Polyline(polyline))  => Coordinates::from_polyline(polyline, 5).unwrap(),
+                (6, Coordinates::Polyline6(polyline)) => Coordinates::from_polyline(polyline, 6).unwrap(),
+                _ => panic!("unexpected coordinates {coordinates:?} for precision {precision}"),
+            };
+            match decoded {
+                Coordinates::Multi(decoded) => {
+                    assert_eq!(locations.len(), decoded.len());
+                    assert!(locations.iter().zip(decoded.iter()).all(|(a, b)| a.haversine_distance(b) <= tolerance));
+                },
+                _ => panic!("expected explicit coordinates"),
+            }
+        }
+        assert!(matches!(Coordinates::from_locations_as_polyline(&locations, 7), Err(Error::PolylineError(_))));
+        assert!(matches!(Coordinates::from_locations_as_polyline(&[], 5), Ok(Coordinates::Polyline(p)) if p.is_empty()));
+    }
+    #[test]
+    fn decode_encoded_geometries() {
+        let expected = vec![Location::new(-120.2, 38.5), Location::new(-120.95, 40.7), Location::new(-126.453, 43.252)];
+        let geometry = Geometry::Encoded("_p~iF~ps|U_ulLnnqC_mqNvxq`@".to_string());
+        assert_eq!(expected, geometry.decode().unwrap());
+        assert_eq!(expected, geometry.decode_with_precision(5).unwrap());
+
+        let polyline6 = Geometry::Encoded(encode_polyline(&expected, 6));
+        assert_eq!(expected, polyline6.decode_with_precision(6).unwrap());
+        assert_eq!("_p~iF~ps|U_ulLnnqC_mqNvxq`@", encode_polyline(&geometry.decode().unwrap(), 5));
+        assert!(Geometry::Encoded("_p~iF~".to_string()).decode().is_err());
+    }
+    #[test]
+    fn decode_explicit_geometries() {
+        let geometry = serde_json::from_str::<Geometry>(r#"{"type":"LineString","coordinates":[[-1.5,44.25],[-1.0,44.0]]}"#).unwrap();
+        assert_eq!(vec![Location::new(-1.5, 44.25), Location::new(-1.0, 44.0)], geometry.decode().unwrap());
+        assert_eq!(geometry.decode().unwrap(), geometry.decode_with_precision(6).unwrap());
+    }
+
+    fn step(extra: &str) -> RouteStep {
+        step_with_intersections("[]", extra)
+    }
+    fn step_with_intersections(intersections: &str, extra: &str) -> RouteStep {
+        let text = format!(r#"
+        {{"distance":152.3,
+          "duration":20.1,
+          "weight":20.1,
+          "geometry":"slluHq`qZ~eChbD",
+          "name":"Jagersstraat",
+          "mode":"driving",
+          "maneuver":{{"location":[4.516091,50.859136],"bearing_before":0,"bearing_after":90,"type":"depart"}},
+          "intersections":{intersections}
+          {extra}
+        }}"#);
+        serde_json::from_str(&text).unwrap()
+    }
+
+    #[test]
+    fn lane_guidance_of_multi_lane_intersection() {
+        let step = step_with_intersections(r#"[
+            {"location":[4.516091,50.859136],"bearings":[0,90,180],"entry":[true,true,false],"in":2,"out":0,
+             "lanes":[
+                {"indications":["left"],"valid":false},
+                {"indications":["straight","left"],"valid":true},
+                {"indications":["none"],"valid":true},
+                {"indications":["right"],"valid":false}
+             ]},
+            {"location":[4.516,50.86],"bearings":[0,180],"entry":[true,false],"in":1,"out":0}
+        ]"#, "");
+        let lanes = step.lane_guidance().unwrap();
+        assert_eq!(lanes, vec![
+            LaneGuidance { indications: vec![DirectionChange::Left], valid: false },
+            LaneGuidance { indications: vec![DirectionChange::Straight, DirectionChange::Left], valid: true },
+            LaneGuidance { indications: vec![DirectionChange::None], valid: true },
+            LaneGuidance { indications: vec![DirectionChange::Right], valid: false },
+        ]);
+    }
+    #[test]
+    fn waypoints_are_deduplicated_by_hint() {
+        let waypoint = |hint: &str, longitude: f32| serde_json::from_str::<Waypoint>(&format!(
+            r#"{{"hint":"{hint}","distance":1.0,"name":"Jagersstraat","location":[{longitude},50.859136]}}"#)).unwrap();
+
+        let unique = [waypoint("abc", 4.516091), waypoint("abc", 4.516092), waypoint("def", 4.516091)]
+            .into_iter()
+            .collect::<std::collections::HashSet<_>>();
+        assert_eq!(2, unique.len());
+        assert!(waypoint("abc", 4.5) == waypoint("abc", 4.6));
+        assert!(waypoint("abc", 4.5) != waypoint("def", 4.5));
+    }
+    #[test]
+    fn road_label_combines_reference_and_name() {
+        let mut step = step("");
+        step.name = "Brussels Ring".to_string();
+        assert_eq!("Brussels Ring", step.road_label());
+        step.reference = Some("A4".to_string());
+        assert_eq!("A4 / Brussels Ring", step.road_label());
+        step.name.clear();
+        assert_eq!("A4", step.road_label());
+        step.reference = Some(String::new());
+        assert_eq!("", step.road_label());
+    }
+    #[test]
+    fn geometry_points_of_polyline_step() {
+        let points = step("").geometry_points(Geometries::Polyline).unwrap();
+        assert_eq!("4.51609,50.85914;4.48996,50.83754", Coordinates::Multi(points).to_string());
+    }
+    #[test]
+    fn geometry_points_of_polyline6_step() {
+        let points = vec![Location::new(4.516091, 50.859136), Location::new(4.489963, 50.837543)];
+        let mut step = step("");
+        step.geometry = Geometry::Encoded(encode_polyline(&points, 6));
+        assert_eq!(points, step.geometry_points(Geometries::Polyline6).unwrap());
+        assert!(matches!(step.geometry_points(Geometries::GeoJson), Err(Error::PolylineError(_))));
+    }
+    #[test]
+    fn geometry_points_of_geojson_step() {
+        let mut step = step("");
+        step.geometry = serde_json::from_str(r#"{"type":"LineString","coordinates":[[4.5,50.8],[4.6,50.7]]}"#).unwrap();
+        assert_eq!(vec![Location::new(4.5, 50.8), Location::new(4.6, 50.7)], step.geometry_points(Geometries::GeoJson).unwrap());
+    }
+    #[test]
+    fn no_lane_guidance_without_lanes() {
+        assert!(step("").lane_guidance().is_none());
+    }
+
+    #[test]
+    fn spoken_name_falls_back_on_name() {
+        assert_eq!("Jagersstraat", step("").spoken_name());
+    }
+    #[test]
+    fn spoken_name_prefers_pronunciation() {
+        let step = step(r#","pronunciation":"ˈjaːɣərsˌstraːt""#);
+        assert_eq!("ˈjaːɣərsˌstraːt", step.spoken_name());
+    }
+    #[test]
+    fn spoken_rotary_name_is_none_when_not_a_rotary() {
+        assert_eq!(None, step("").spoken_rotary_name());
+    }
+    #[test]
+    fn spoken_rotary_name_falls_back_on_rotary_name() {
+        let step = step(r#","rotary_name":"Place de l'Etoile""#);
+        assert_eq!(Some("Place de l'Etoile"), step.spoken_rotary_name());
+    }
+    #[test]
+    fn spoken_rotary_name_prefers_rotary_pronunciation() {
+        let step = step(r#","rotary_name":"Place de l'Etoile","rotary_pronunciation":"plas də letwal""#);
+        assert_eq!(Some("plas də letwal"), step.spoken_rotary_name());
+    }
+    #[test]
+    fn motorway_signposts_round_trip() {
+        let step = step(r#","destinations":"A1: Paris","exits":"12""#);
+        assert_eq!(Some("A1: Paris"), step.destinations.as_deref());
+        assert_eq!(Some("12"), step.exits.as_deref());
+
+        let step = serde_json::from_str::<RouteStep>(&serde_json::to_string(&step).unwrap()).unwrap();
+        assert_eq!(Some("A1: Paris"), step.destinations.as_deref());
+        assert_eq!(Some("12"), step.exits.as_deref());
+    }
+    #[test]
+    fn unknown_maneuver_types_are_kept() {
+        let step = step("");
+        assert_eq!(ManeuverType::Depart, step.maneuver.maneuver_type);
+
+        let text = serde_json::to_string(&step).unwrap().replace(r#""type":"depart""#, r#""type":"exit passing place""#);
+        let step = serde_json::from_str::<RouteStep>(&text).unwrap();
+        assert_eq!(ManeuverType::Unknown("exit passing place".to_string()), step.maneuver.maneuver_type);
+        assert_eq!("exit passing place", step.maneuver.maneuver_type.to_string());
+        assert_eq!(r#""exit passing place""#, serde_json::to_string(&step.maneuver.maneuver_type).unwrap());
+    }
+    #[test]
+    fn unknown_direction_changes_are_kept() {
+        assert_eq!(DirectionChange::SlightLeft, serde_json::from_str(r#""slight left""#).unwrap());
+        assert_eq!(DirectionChange::Unknown("merge left".to_string()), serde_json::from_str(r#""merge left""#).unwrap());
+    }
+    #[test]
+    fn signposts_are_optional() {
+        let step = step("");
+        assert_eq!(None, step.destinations);
+        assert_eq!(None, step.exits);
+    }
+
+    fn geometry(text: &str) -> GeoJsonGeometry {
+        serde_json::from_str(text).unwrap()
+    }
+    fn longitudes(geometry: &GeoJsonGeometry) -> Vec<f32> {
+        geometry.all_points().iter().map(|l| l.longitude).collect()
+    }
+
+    #[test]
+    fn all_points_of_point() {
+        let g = geometry(r#"{"type":"Point","coordinates":[1.0,2.0,3.0]}"#);
+        assert_eq!(vec![1.0], longitudes(&g));
+        assert_eq!(1, g.point_count());
+    }
+    #[test]
+    fn all_points_of_linestring() {
+        let g = geometry(r#"{"type":"LineString","coordinates":[[1.0,2.0],[3.0,4.0]]}"#);
+        assert_eq!(vec![1.0, 3.0], longitudes(&g));
+        assert_eq!(2, g.point_count());
+    }
+    #[test]
+    fn all_points_of_multipoint() {
+        let g = geometry(r#"{"type":"MultiPoint","coordinates":[[1.0,2.0],[3.0,4.0]]}"#);
+        assert_eq!(vec![1.0, 3.0], longitudes(&g));
+        assert_eq!(2, g.point_count());
+    }
+    #[test]
+    fn all_points_of_polygon() {
+        let g = geometry(r#"{"type":"Polygon","coordinates":[[[1.0,2.0],[3.0,4.0],[1.0,2.0]],[[5.0,6.0]]]}"#);
+        assert_eq!(vec![1.0, 3.0, 1.0, 5.0], longitudes(&g));
+        assert_eq!(4, g.point_count());
+    }
+    #[test]
+    fn all_points_of_multilinestring() {
+        let g = geometry(r#"{"type":"MultiLineString","coordinates":[[[1.0,2.0],[3.0,4.0]],[[5.0,6.0]]]}"#);
+        assert_eq!(vec![1.0, 3.0, 5.0], longitudes(&g));
+        assert_eq!(3, g.point_count());
+    }
+    #[test]
+    fn all_points_of_multipolygon() {
+        let g = geometry(r#"{"type":"MultiPolygon","coordinates":[[[[1.0,2.0],[3.0,4.0]]],[[[5.0,6.0]],[[7.0,8.0]]]]}"#);
+        assert_eq!(vec![1.0, 3.0, 5.0, 7.0], longitudes(&g));
+        assert_eq!(4, g.point_count());
+    }
+
+    #[test]
+    fn speed_profile_pairs_speeds_with_segment_starts() {
+        let leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        let profile = leg.speed_profile(Geometries::GeoJson).unwrap();
+        assert_eq!(profile, vec![
+            (Location::new(0.0, 0.0), 10.0),
+            (Location::new(1.0, 0.0), 20.0),
+            (Location::new(2.0, 0.0), 30.0),
+        ]);
+    }
+    #[test]
+    fn speed_profile_validates_the_number_of_speeds() {
+        let mut leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        leg.annotation.as_mut().unwrap().speed = vec![10.0, 20.0];
+        assert!(matches!(leg.speed_profile(Geometries::GeoJson), 
+            Err(Error::MisalignedAnnotation { annotation: "speed", expected: 3, actual: 2 })));
+
+        leg.steps.clear();
+        assert!(leg.speed_profile(Geometries::GeoJson).unwrap().is_empty());
+    }
+
+    #[test]
+    fn annotated_segments_zip_geometry_and_annotations() {
+        let mut leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        leg.annotation.as_mut().unwrap().duration = vec![Seconds(1.0), Seconds(0.5), Seconds(0.5)];
+        let segments = leg.annotated_segments(Geometries::GeoJson).unwrap();
+        assert_eq!(3, segments.len());
+        assert_eq!(AnnotatedSegment {
+            from: Location::new(1.0, 0.0), 
+            to: Location::new(2.0, 0.0), 
+            distance: None, 
+            duration: Some(Seconds(0.5)), 
+            weight: None, 
+            speed: Some(20.0)
+        }, segments[1]);
+    }
+    #[test]
+    fn annotated_segments_validate_the_lengths() {
+        let mut leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        leg.annotation.as_mut().unwrap().weight = vec![1.0, 2.0];
+        assert!(matches!(leg.annotated_segments(Geometries::GeoJson), 
+            Err(Error::MisalignedAnnotation { annotation: "weight", expected: 3, actual: 2 })));
+
+        leg.annotation = None;
+        assert!(matches!(leg.annotated_segments(Geometries::GeoJson), Err(Error::MissingData("annotation"))));
+    }
+    #[test]
+    fn annotated_segments_of_polyline6_steps() {
+        let mut leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        for step in leg.steps.iter_mut() {
+            let points = step.geometry_points(Geometries::GeoJson).unwrap();
+            step.geometry = Geometry::Encoded(encode_polyline(&points, 6));
+        }
+        let segments = leg.annotated_segments(Geometries::Polyline6).unwrap();
+        assert_eq!(3, segments.len());
+        assert_eq!(Location::new(2.0, 0.0), segments[1].to);
+        assert_eq!(Some(30.0), segments[2].speed);
+    }
+
+    #[test]
+    fn maneuvers_of_multi_step_leg() {
+        let leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        assert_eq!(leg.maneuver_locations(), vec![
+            Location::new(0.0, 0.0),
+            Location::new(1.0, 0.0),
+            Location::new(3.0, 0.0),
+        ]);
+        assert_eq!(leg.maneuver_types(), vec![ManeuverType::Depart, ManeuverType::Turn, ManeuverType::Arrive]);
+    }
+
+    #[test]
+    fn depart_and_arrive_steps_without_modifier() {
+        let leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        let depart = leg.depart_step().unwrap();
+        let arrive = leg.arrive_step().unwrap();
+        assert!(depart.maneuver.is_depart() && depart.maneuver.modifier.is_none());
+        assert!(arrive.maneuver.is_arrive() && arrive.maneuver.modifier.is_none());
+        assert!(!leg.steps[1].maneuver.is_depart() && !leg.steps[1].maneuver.is_arrive());
+    }
+    #[test]
+    fn no_depart_nor_arrive_step_without_steps() {
+        let mut leg = serde_json::from_str::<RouteLeg>(THREE_STEPS_LEG).unwrap();
+        leg.steps.clear();
+        assert!(leg.depart_step().is_none());
+        assert!(leg.arrive_step().is_none());
+    }
+
+    const THREE_STEPS_LEG: &str = r#"
+        {"distance":3.0, "duration":3.0, "weight":3.0, "summary":"",
+         "steps":[
+            {"distance":1.0, "duration":1.0, "weight":1.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[0.0,0.0],[1.0,0.0]]},
+             "maneuver":{"location":[0.0,0.0],"bearing_before":0,"bearing_after":90,"type":"depart"}},
+            {"distance":2.0, "duration":2.0, "weight":2.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[1.0,0.0],[2.0,0.0],[3.0,0.0]]},
+             "maneuver":{"location":[1.0,0.0],"bearing_before":90,"bearing_after":90,"type":"turn","modifier":"straight"}},
+            {"distance":0.0, "duration":0.0, "weight":0.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[3.0,0.0],[3.0,0.0]]},
+             "maneuver":{"location":[3.0,0.0],"bearing_before":90,"bearing_after":0,"type":"arrive"}}
+         ],
+         "annotation":{"speed":[10.0,20.0,30.0]}
+        }"#;
+
+    fn route(geometry: &str) -> Route {
+        let text = format!(r#"{{"geometry":{geometry},"legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}}"#);
+        serde_json::from_str(&text).unwrap()
+    }
+    #[test]
+    fn to_wkt_with_geojson_geometry() {
+        let route = route(r#"{"type":"LineString","coordinates":[[-1.301955,44.189087],[-1.007841,44.05135]]}"#);
+        assert_eq!("LINESTRING(-1.301955 44.189087, -1.007841 44.05135)", route.to_wkt(Geometries::GeoJson).unwrap());
+    }
+    #[test]
+    fn to_wkt_with_encoded_geometry() {
+        let route = route(r#""_p~iF~ps|U_ulLnnqC_mqNvxq`@""#);
+        assert_eq!("LINESTRING(-120.2 38.5, -120.95 40.7, -126.453 43.252)", route.to_wkt(Geometries::Polyline).unwrap());
+    }
+    #[test]
+    fn to_wkt_with_polyline6_geometry() {
+        let points = vec![Location::new(4.5625, 50.84375), Location::new(4.578125, 50.859375)];
+        let route = route(&format!("{:?}", encode_polyline(&points, 6)));
+        assert_eq!("LINESTRING(4.5625 50.84375, 4.578125 50.859375)", route.to_wkt(Geometries::Polyline6).unwrap());
+    }
+
+    #[test]
+    fn to_geojson_preserves_elevation() {
+        let route = route(r#"{"type":"LineString","coordinates":[[-1.301955,44.189087,12.5],[-1.007841,44.05135,30.0]]}"#);
+        let geojson = route.to_geojson(Geometries::GeoJson).unwrap();
+        assert!(geojson.contains("[-1.301955,44.189087,12.5]"));
+        let feature: serde_json::Value = serde_json::from_str(&geojson).unwrap();
+        assert_eq!("Feature", feature["type"]);
+        assert_eq!("LineString", feature["geometry"]["type"]);
+        assert_eq!(serde_json::json!([[-1.301955,44.189087,12.5],[-1.007841,44.05135,30.0]]), feature["geometry"]["coordinates"]);
+        assert_eq!("routability", feature["properties"]["weight_name"]);
+    }
+    #[test]
+    fn to_geojson_with_encoded_geometry() {
+        let route = route(r#""_p~iF~ps|U_ulLnnqC""#);
+        let feature: serde_json::Value = serde_json::from_str(&route.to_geojson(Geometries::Polyline).unwrap()).unwrap();
+        assert_eq!(serde_json::json!([[-120.2,38.5],[-120.95,40.7]]), feature["geometry"]["coordinates"]);
+
+        let feature: serde_json::Value = serde_json::from_str(&route.to_geojson(Geometries::Polyline6).unwrap()).unwrap();
+        assert_eq!(serde_json::json!([[-12.02,3.85],[-12.095,4.07]]), feature["geometry"]["coordinates"]);
+        assert!(matches!(route.to_geojson(Geometries::GeoJson), Err(Error::PolylineError(_))));
+    }
+
+    #[test]
+    fn simplify_to_zoom_drops_invisible_points() {
+        let route = route(r#"{"type":"LineString","coordinates":[[0.0,0.0],[0.5,0.0001],[1.0,0.0],[1.5,0.5],[2.0,0.0]]}"#);
+        // at zoom 4, 0.0001° is way below one pixel but 0.3° is not
+        assert_eq!(route.simplify_to_zoom(4, Geometries::GeoJson).unwrap(), vec![
+            Location::new(0.0, 0.0),
+            Location::new(1.0, 0.0),
+            Location::new(1.5, 0.5),
+            Location::new(2.0, 0.0),
+        ]);
+        // at zoom 18, even the smallest bump is visible
+        assert_eq!(5, route.simplify_to_zoom(18, Geometries::GeoJson).unwrap().len());
+        // at zoom 0, everything is a single pixel
+        assert_eq!(2, route.simplify_to_zoom(0, Geometries::GeoJson).unwrap().len());
+    }
+    #[test]
+    fn simplify_to_zoom_of_tiny_geometries() {
+        assert_eq!(1, route(r#"{"type":"Point","coordinates":[1.0,2.0]}"#).simplify_to_zoom(10, Geometries::GeoJson).unwrap().len());
+        assert_eq!(0, route(r#"{"type":"LineString","coordinates":[]}"#).simplify_to_zoom(10, Geometries::GeoJson).unwrap().len());
+    }
+    #[test]
+    fn simplify_to_zoom_of_polyline6_geometry() {
+        let points = vec![Location::new(0.0, 0.0), Location::new(0.5, 0.0001), Location::new(1.0, 0.0)];
+        let route = route(&format!("{:?}", encode_polyline(&points, 6)));
+        assert_eq!(vec![Location::new(0.0, 0.0), Location::new(1.0, 0.0)], route.simplify_to_zoom(4, Geometries::Polyline6).unwrap());
+        assert_eq!(points, route.simplify_to_zoom(18, Geometries::Polyline6).unwrap());
+    }
+
+    #[test]
+    fn units_convert_and_display() {
+        assert_eq!(1.5, Meters(1500.0).kilometers());
+        assert_eq!(1.0, Meters(1609.344).miles());
+        assert_eq!(1.5, Seconds(90.0).minutes());
+        assert_eq!("12.5 m", Meters(12.5).to_string());
+        assert_eq!("30 s", Seconds(30.0).to_string());
+        assert_eq!(Meters(3.0), [Meters(1.0), Meters(2.0)].iter().sum());
+        assert_eq!(2.0, Seconds(60.0) / Seconds(30.0));
+
+        let step = serde_json::from_str::<Meters>("42.5").unwrap();
+        assert_eq!(Meters(42.5), step);
+        assert_eq!("42.5", serde_json::to_string(&step).unwrap());
+    }
+    #[test]
+    fn cumulative_at_step_boundaries() {
+        let route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        assert_eq!(vec![(Meters(0.0), Seconds(0.0)), (Meters(1.0), Seconds(30.0)), (Meters(2.0), Seconds(40.0)), (Meters(2.0), Seconds(40.0))], route.cumulative());
+    }
+    #[test]
+    fn cumulative_falls_back_to_legs_without_steps() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        route.legs[0].steps.clear();
+        route.legs.push(route.legs[0].clone());
+        assert_eq!(vec![(Meters(0.0), Seconds(0.0)), (Meters(2.0), Seconds(40.0)), (Meters(4.0), Seconds(80.0))], route.cumulative());
+    }
+
+    #[test]
+    fn summary_round_trip() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        route.legs[0].steps[0].name = "Rue Haute".to_string();
+        route.legs[0].steps[2].name = "Rue Blaes".to_string();
+        let summary = route.summarize(Geometries::GeoJson).unwrap();
+        assert_eq!(RouteSummary {
+            distance: 2.0,
+            duration: 40.0,
+            geometry_polyline: "??_ibE?_ibE?".to_string(),
+            waypoint_names: vec!["Rue Haute".to_string(), "Rue Blaes".to_string()],
+        }, summary);
+
+        let json = serde_json::to_string(&summary).unwrap();
+        assert_eq!(summary, serde_json::from_str::<RouteSummary>(&json).unwrap());
+
+        route.geometry = Geometry::Encoded(encode_polyline(&route.geometry.decode_as(Geometries::GeoJson).unwrap(), 6));
+        assert_eq!(summary, route.summarize(Geometries::Polyline6).unwrap());
+    }
+    #[test]
+    fn summary_without_steps_has_no_waypoint_names() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        route.legs[0].steps.clear();
+        assert!(route.summarize(Geometries::GeoJson).unwrap().waypoint_names.is_empty());
+    }
+    #[test]
+    fn step_consistency() {
+        let mut step = step("");
+        assert!(step.is_consistent());
+        (step.distance, step.duration) = (Meters(0.0), Seconds(0.0));
+        assert!(step.is_consistent());
+        (step.distance, step.duration) = (Meters(0.5), Seconds(0.0));
+        assert!(step.is_consistent());
+        (step.distance, step.duration) = (Meters(152.3), Seconds(0.0));
+        assert!(!step.is_consistent());
+        (step.distance, step.duration) = (Meters(0.0), Seconds(20.1));
+        assert!(!step.is_consistent());
+    }
+    #[test]
+    fn suspicious_steps_are_numbered_across_legs() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        assert!(route.suspicious_steps().is_empty());
+        (route.legs[0].steps[1].distance, route.legs[0].steps[1].duration) = (Meters(5.0), Seconds(0.0));
+        route.legs.push(route.legs[0].clone());
+        route.legs[1].steps[0].distance = Meters(0.0);
+        assert_eq!(vec![1, 3, 4], route.suspicious_steps());
+    }
+    #[test]
+    fn detour_ratio_of_a_route() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        let waypoint = |latitude: f32| serde_json::from_str::<Waypoint>(
+            &format!(r#"{{"hint":"","distance":0.0,"name":"","location":[0.0,{latitude}]}}"#)).unwrap();
+        let waypoints = [waypoint(0.0), waypoint(0.5), waypoint(1.0)];
+        route.distance = Meters(1.5 * Location::new(0.0, 0.0).haversine_distance(&Location::new(0.0, 1.0)));
+        assert!((route.detour_ratio(&waypoints).unwrap() - 1.5).abs() < 1e-6);
+
+        assert_eq!(None, route.detour_ratio(&[]));
+        assert_eq!(None, route.detour_ratio(&waypoints[..1]));
+        assert_eq!(None, route.detour_ratio(&[waypoint(1.0), waypoint(1.0)]));
+    }
+    #[test]
+    fn unbiased_route() {
+        let route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        assert_eq!(1.0, route.weight_duration_ratio());
+        assert!(!route.is_biased(0.0));
+    }
+    #[test]
+    fn biased_route() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        route.weight = 50.0;
+        assert_eq!(1.25, route.weight_duration_ratio());
+        assert!(route.is_biased(0.2));
+        assert!(!route.is_biased(0.3));
+
+        route.duration = Seconds(0.0);
+        assert_eq!(1.0, route.weight_duration_ratio());
+    }
+
+    const TIMED_ROUTE: &str = r#"
+    {"geometry":{"type":"LineString","coordinates":[[0.0,0.0],[0.0,1.0],[0.0,2.0]]},
+     "legs":[{"distance":2.0, "duration":40.0, "weight":40.0, "summary":"",
+        "steps":[
+            {"distance":1.0, "duration":30.0, "weight":30.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[0.0,0.0],[0.0,1.0]]},
+             "maneuver":{"location":[0.0,0.0],"bearing_before":0,"bearing_after":0,"type":"depart"}},
+            {"distance":1.0, "duration":10.0, "weight":10.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[0.0,1.0],[0.0,2.0]]},
+             "maneuver":{"location":[0.0,1.0],"bearing_before":0,"bearing_after":0,"type":"turn","modifier":"straight"}},
+            {"distance":0.0, "duration":0.0, "weight":0.0, "name":"", "mode":"driving", "intersections":[],
+             "geometry":{"type":"LineString","coordinates":[[0.0,2.0],[0.0,2.0]]},
+             "maneuver":{"location":[0.0,2.0],"bearing_before":0,"bearing_after":0,"type":"arrive"}}
+        ]}],
+     "weight_name":"routability","weight":40.0,"duration":40.0,"distance":2.0}"#;
+
+    #[test]
+    fn point_at_fraction_of_distance() {
+        let route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        let at = |f: f32| route.point_at_fraction(f, FractionBasis::Distance, Geometries::GeoJson).unwrap().latitude;
+        assert_close(0.0, f64::from(at(0.0)),  1e-5);
+        assert_close(0.5, f64::from(at(0.25)), 1e-5);
+        assert_close(1.0, f64::from(at(0.5)),  1e-5);
+        assert_close(2.0, f64::from(at(1.0)),  1e-5);
+    }
+    #[test]
+    fn point_at_fraction_of_duration() {
+        let route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        let at = |f: f32| route.point_at_fraction(f, FractionBasis::Duration, Geometries::GeoJson).unwrap().latitude;
+        assert_close(0.0,         f64::from(at(0.0)),  1e-5);
+        assert_close(2.0 / 3.0,   f64::from(at(0.5)),  1e-5);
+        assert_close(1.5,         f64::from(at(0.875)),1e-5);
+        assert_close(2.0,         f64::from(at(1.0)),  1e-5);
+    }
+    #[test]
+    fn point_at_fraction_of_polyline6_geometries() {
+        let mut route = serde_json::from_str::<Route>(TIMED_ROUTE).unwrap();
+        route.geometry = Geometry::Encoded(encode_polyline(&route.geometry.decode_as(Geometries::GeoJson).unwrap(), 6));
+        for step in route.legs[0].steps.iter_mut() {
+            step.geometry = Geometry::Encoded(encode_polyline(&step.geometry_points(Geometries::GeoJson).unwrap(), 6));
+        }
+        let at = |f: f32, by: FractionBasis| route.point_at_fraction(f, by, Geometries::Polyline6).unwrap().latitude;
+        assert_close(1.0,       f64::from(at(0.5, FractionBasis::Distance)), 1e-5);
+        assert_close(2.0 / 3.0, f64::from(at(0.5, FractionBasis::Duration)), 1e-5);
+        assert_eq!(None, route.point_at_fraction(0.5, FractionBasis::Distance, Geometries::GeoJson));
+    }
+}
@@ -1,9 +1,13 @@
 //! This module describes the general options that are applicable to all requests
 //! of all services in OSRM.
 
+use std::str::FromStr;
+
 use displaythis::Display;
 use serde::{Serialize, Deserialize};
 
+use crate::{Decimal, Error};
+
 /// Which is the service being used
 #[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
 pub enum Service {
@@ -38,39 +42,100 @@ pub struct BearingRequest {
 }
 
 /// Limits the search to given radius in meters.
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
 pub enum Radius {
     /// The default value
-    #[display("unlimited")]
+    #[default]
     Unlimited,
     /// Limits the search to a radius of ? meters >= 0
-    #[display("{0}")]
     Limited(f64),
 }
-impl Default for Radius {
-    fn default() -> Self { Self::Unlimited }
+impl std::fmt::Display for Radius {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unlimited  => write!(f, "unlimited"),
+            Self::Limited(r) => write!(f, "{}", Decimal(*r)),
+        }
+    }
 }
 
 /// Keep waypoints on curb side
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, Default)]
 pub enum Approach {
+    #[default]
     #[display("unrestricted")]
     Unrestricted, 
     #[display("curb")]
     Curb, 
 }
-impl Default for Approach {
-    fn default() -> Self { Self::Unrestricted }
+impl FromStr for Approach {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unrestricted" => Ok(Self::Unrestricted),
+            "curb"         => Ok(Self::Curb),
+            _ => Err(Error::ParseError(format!("approach '{s}' (expected unrestricted or curb)")))
+        }
+    }
 }
 
 /// Default snapping avoids is_startpoint (see profile) edges, any will snap to any edge in the graph
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, Default)]
 pub enum Snapping {
+    #[default]
     #[display("default")]
     Default, 
     #[display("any")]
     Any, 
 }
-impl Default for Snapping {
-    fn default() -> Self { Self::Default }
-}
\ No newline at end of file
+impl FromStr for Snapping {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "any"     => Ok(Self::Any),
+            _ => Err(Error::ParseError(format!("snapping '{s}' (expected default or any)")))
+        }
+    }
+}
+
+/// The classes of roads which can be avoided by means of the `exclude` option.
+/// Which classes are actually available depends on the profile the backend
+/// was configured with (the ones listed here are those of the stock car profile).
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RoadClass {
+    /// Toll roads
+    #[display("toll")]
+    Toll,
+    /// Motorways
+    #[display("motorway")]
+    Motorway,
+    /// Ferry routes
+    #[display("ferry")]
+    Ferry,
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Approach, Snapping};
+
+    #[test]
+    fn approach_parses_its_display() {
+        for approach in [Approach::Unrestricted, Approach::Curb] {
+            let parsed: Approach = approach.to_string().parse().unwrap();
+            assert_eq!(approach.to_string(), parsed.to_string());
+        }
+        assert!("kerb".parse::<Approach>().is_err());
+    }
+    #[test]
+    fn snapping_parses_its_display() {
+        for snapping in [Snapping::Default, Snapping::Any] {
+            let parsed: Snapping = snapping.to_string().parse().unwrap();
+            assert_eq!(snapping.to_string(), parsed.to_string());
+        }
+        assert!("Any".parse::<Snapping>().is_err());
+    }
+}
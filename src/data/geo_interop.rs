@@ -0,0 +1,74 @@
+//! This module provides the conversions between the types of this crate and 
+//! those of the `geo` crate (only available with the `geo` feature). These let
+//! you pipe the output of OSRM directly into the algorithms of `geo` (length,
+//! simplification, containment,...).
+
+use geo::{Coord, LineString, Point};
+
+use crate::{Coordinates, Error, Geometries, Location, Route};
+
+impl From<Location> for Point<f64> {
+    fn from(location: Location) -> Self {
+        Point::new(f64::from(location.longitude), f64::from(location.latitude))
+    }
+}
+impl From<Point<f64>> for Location {
+    fn from(point: Point<f64>) -> Self {
+        Location::new(point.x() as f32, point.y() as f32)
+    }
+}
+impl From<Location> for Coord<f64> {
+    fn from(location: Location) -> Self {
+        Coord { x: f64::from(location.longitude), y: f64::from(location.latitude) }
+    }
+}
+impl From<Coord<f64>> for Location {
+    fn from(coord: Coord<f64>) -> Self {
+        Location::new(coord.x as f32, coord.y as f32)
+    }
+}
+impl Route {
+    /// Decodes the geometry of the route as a `LineString` of the `geo` crate.
+    /// `geometries` is the format in which the geometry was requested.
+    pub fn to_line_string(&self, geometries: Geometries) -> Result<LineString<f64>, Error> {
+        let points = self.geometry.decode_as(geometries)?;
+        Ok(points.into_iter().map(Coord::from).collect())
+    }
+}
+impl From<LineString<f64>> for Coordinates {
+    fn from(line: LineString<f64>) -> Self {
+        Coordinates::Multi(line.into_iter().map(Location::from).collect())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use geo::Point;
+    use crate::{Coordinates, Error, Geometries, Location, Route};
+
+    #[test]
+    fn location_to_point_and_back() {
+        let point = Point::<f64>::from(Location::new(4.5, 50.75));
+        assert_eq!(Point::new(4.5, 50.75), point);
+        assert_eq!(Location::new(4.5, 50.75), Location::from(point));
+    }
+
+    #[test]
+    fn route_to_linestring_and_back_to_coordinates() {
+        let text = r#"{"geometry":"_p~iF~ps|U_ulLnnqC_mqNvxq`@","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}"#;
+        let route = serde_json::from_str::<Route>(text).unwrap();
+        let line  = route.to_line_string(Geometries::Polyline).unwrap();
+        assert_eq!(3, line.0.len());
+
+        let coordinates = Coordinates::from(line);
+        assert_eq!("-120.2,38.5;-120.95,40.7;-126.453,43.252", coordinates.to_string());
+    }
+    #[test]
+    fn route_to_linestring_in_the_requested_format() {
+        let text = r#"{"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}"#;
+        let route = serde_json::from_str::<Route>(text).unwrap();
+        let line  = route.to_line_string(Geometries::Polyline6).unwrap();
+        assert_eq!("-12.02,3.85;-12.095,4.07", Coordinates::from(line).to_string());
+        assert!(matches!(route.to_line_string(Geometries::GeoJson), Err(Error::PolylineError(_))));
+    }
+}
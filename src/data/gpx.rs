@@ -0,0 +1,80 @@
+//! This module exports routes in the GPS Exchange Format (GPX) which is what
+//! handheld GPS units and fitness apps understand.
+
+use std::fmt::Write;
+
+use crate::{Error, Geometries, Location, Route, Waypoint, Decimal};
+
+/// Renders the given waypoints (as `<wpt>`) and routes (each one as a `<trk>`
+/// made of one segment) as a GPX 1.1 document. `geometries` is the format in 
+/// which the geometries of the routes were requested.
+pub(crate) fn to_gpx<'a>(waypoints: impl IntoIterator<Item = &'a Waypoint>, routes: &[&Route], geometries: Geometries) -> Result<String, Error> {
+    let mut gpx = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#, "\n",
+        r#"<gpx version="1.1" creator="osrm_client" xmlns="http://www.topografix.com/GPX/1/1">"#, "\n"));
+
+    for waypoint in waypoints {
+        let Location { longitude, latitude } = waypoint.location;
+        let (lat, lon) = (Decimal(latitude), Decimal(longitude));
+        let _ = writeln!(gpx, r#"  <wpt lat="{lat}" lon="{lon}"><name>{}</name></wpt>"#, escape(&waypoint.name));
+    }
+    for route in routes {
+        gpx.push_str("  <trk>\n    <trkseg>\n");
+        for Location { longitude, latitude } in route.geometry.decode_as(geometries)? {
+            let (lat, lon) = (Decimal(latitude), Decimal(longitude));
+            let _ = writeln!(gpx, r#"      <trkpt lat="{lat}" lon="{lon}"/>"#);
+        }
+        gpx.push_str("    </trkseg>\n  </trk>\n");
+    }
+    gpx.push_str("</gpx>\n");
+    Ok(gpx)
+}
+
+/// Escapes the characters having a special meaning in xml
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&'  => escaped.push_str("&amp;"),
+            '<'  => escaped.push_str("&lt;"),
+            '>'  => escaped.push_str("&gt;"),
+            '"'  => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            _    => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, Geometries, Route, Waypoint};
+    use super::to_gpx;
+
+    #[test]
+    fn gpx_with_waypoints_and_track() {
+        let route: Route = serde_json::from_str(r#"{"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}"#).unwrap();
+        let waypoint: Waypoint = serde_json::from_str(r#"{"hint":"","distance":1.0,"name":"Rue <Haute> & co","location":[4.35,50.85]}"#).unwrap();
+
+        let expected = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gpx version="1.1" creator="osrm_client" xmlns="http://www.topografix.com/GPX/1/1">
+  <wpt lat="50.85" lon="4.35"><name>Rue &lt;Haute&gt; &amp; co</name></wpt>
+  <trk>
+    <trkseg>
+      <trkpt lat="38.5" lon="-120.2"/>
+      <trkpt lat="40.7" lon="-120.95"/>
+    </trkseg>
+  </trk>
+</gpx>
+"#;
+        assert_eq!(expected, to_gpx([&waypoint], &[&route], Geometries::Polyline).unwrap());
+        assert!(matches!(to_gpx([&waypoint], &[&route], Geometries::GeoJson), Err(Error::PolylineError(_))));
+    }
+    #[test]
+    fn gpx_of_polyline6_track() {
+        let route: Route = serde_json::from_str(r#"{"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}"#).unwrap();
+        let gpx = to_gpx([], &[&route], Geometries::Polyline6).unwrap();
+        assert!(gpx.contains(r#"<trkpt lat="3.85" lon="-12.02"/>"#));
+        assert!(gpx.contains(r#"<trkpt lat="4.07" lon="-12.095"/>"#));
+    }
+}
@@ -2,6 +2,14 @@
 
 mod general_options;
 mod common;
+mod gpx;
+#[cfg(feature = "geo")]
+mod geo_interop;
+#[cfg(feature = "mvt")]
+mod mvt;
 
 pub use general_options::*;
-pub use common::*;
\ No newline at end of file
+pub use common::*;
+#[cfg(feature = "mvt")]
+pub use mvt::*;
+pub(crate) use gpx::to_gpx;
\ No newline at end of file
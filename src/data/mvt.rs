@@ -0,0 +1,448 @@
+//! This module decodes the Mapbox Vector Tiles served by the tile service into
+//! the `speeds` and `turns` layers they comprise (feature `mvt`). It implements
+//! just as much of the protobuf wire format as required to read these tiles
+//! (see https://github.com/mapbox/vector-tile-spec/tree/master/2.1).
+
+use crate::Error;
+
+/// The content of a tile of the tile service
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DecodedTile {
+    /// The size of the tile in the units of the geometries (4096 unless told otherwise)
+    pub extent: u32,
+    /// The road segments of the routing graph
+    pub speeds: Vec<SpeedSegment>,
+    /// The turns at the intersections of the routing graph
+    pub turns: Vec<Turn>,
+}
+
+/// One road segment of the `speeds` layer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SpeedSegment {
+    /// The points of the segment, in tile units (from the top left corner of the tile)
+    pub geometry: Vec<(i32, i32)>,
+    /// The speed on that road segment, in km/h
+    pub speed: u32,
+    /// Whether this segment belongs to a small (< 1000 node) strongly connected component
+    pub is_small: bool,
+    /// The source for the speed value (normally lua profile unless you're using the
+    /// traffic update feature, in which case it contains the stem of the filename
+    /// that supplied the speed value for this segment)
+    pub datasource: String,
+    /// How long this segment takes to traverse, in seconds
+    pub duration: f32,
+    /// How long this segment takes to traverse, in units (may differ from duration
+    /// when artificial biasing is applied in the Lua profiles). Actual routing uses this value.
+    pub weight: f32,
+    /// The name of the road this segment belongs to
+    pub name: String,
+    /// The value of length/weight - analagous to speed, but using the weight value
+    /// rather than duration, rounded to the nearest integer
+    pub rate: f32,
+    /// Whether this segment can be used as a start/endpoint for routes
+    pub is_startpoint: bool,
+}
+
+/// One turn of the `turns` layer
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Turn {
+    /// The location of the intersection, in tile units (from the top left corner of the tile)
+    pub location: (i32, i32),
+    /// The absolute bearing that approaches the intersection. -180 to +180, 0 = North, 90 = East
+    pub bearing_in: i32,
+    /// The angle of the turn, relative to the bearing_in. -180 to +180, 0 = straight
+    /// ahead, 90 = 90-degrees to the right
+    pub turn_angle: i32,
+    /// The time we think it takes to make that turn, in seconds. May be negative
+    pub cost: f32,
+    /// The weight we think it takes to make that turn. May be negative. Actual
+    /// routing uses this value
+    pub weight: f32,
+    /// The type of this turn (values like turn, continue,...). This also exposes
+    /// internal turn types that are never returned with an API response
+    pub turn_type: String,
+    /// The direction modifier of the turn (left, sharp left,...)
+    pub modifier: String,
+}
+
+impl DecodedTile {
+    /// Decodes the given tile (as returned by `TileRequest::send`). The layers
+    /// other than `speeds` and `turns` are ignored, and so are the properties
+    /// which are not documented (the missing ones take their default value).
+    pub fn decode(tile: &[u8]) -> Result<Self, Error> {
+        let mut decoded = DecodedTile { extent: DEFAULT_EXTENT, ..Default::default() };
+        let mut fields  = Fields::new(tile);
+        while let Some((number, value)) = fields.next_field()? {
+            if number == 3 {
+                decode_layer(value.bytes()?, &mut decoded)?;
+            }
+        }
+        Ok(decoded)
+    }
+}
+
+/// The extent of a layer which does not specify it
+const DEFAULT_EXTENT: u32 = 4096;
+
+/// Decodes one layer of the tile into the matching features of `decoded`
+fn decode_layer(layer: &[u8], decoded: &mut DecodedTile) -> Result<(), Error> {
+    let mut name     = String::new();
+    let mut extent   = DEFAULT_EXTENT;
+    let mut keys     = vec![];
+    let mut values   = vec![];
+    let mut features = vec![];
+    let mut fields   = Fields::new(layer);
+    while let Some((number, value)) = fields.next_field()? {
+        match number {
+            1 => name = value.string()?,
+            2 => features.push(value.bytes()?),
+            3 => keys.push(value.string()?),
+            4 => values.push(decode_value(value.bytes()?)?),
+            5 => extent = u32::try_from(value.varint()?).map_err(|_| invalid("extent"))?,
+            _ => {}
+        }
+    }
+    decoded.extent = extent;
+    for feature in features {
+        let (geometry, properties) = decode_feature(feature, &keys, &values)?;
+        let property = |key: &str| properties.iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+            .unwrap_or(&Value::Int(0));
+        match name.as_str() {
+            "speeds" => decoded.speeds.push(SpeedSegment {
+                geometry,
+                speed:         property("speed").as_f64() as u32,
+                is_small:      property("is_small").as_bool(),
+                datasource:    property("datasource").as_string(),
+                duration:      property("duration").as_f64() as f32,
+                weight:        property("weight").as_f64() as f32,
+                name:          property("name").as_string(),
+                rate:          property("rate").as_f64() as f32,
+                is_startpoint: property("is_startpoint").as_bool(),
+            }),
+            "turns" => decoded.turns.push(Turn {
+                location:   geometry.first().copied().unwrap_or_default(),
+                bearing_in: property("bearing_in").as_f64() as i32,
+                turn_angle: property("turn_angle").as_f64() as i32,
+                cost:       property("cost").as_f64() as f32,
+                weight:     property("weight").as_f64() as f32,
+                turn_type:  property("type").as_string(),
+                modifier:   property("modifier").as_string(),
+            }),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// The properties of a feature, by name
+type Properties<'a> = Vec<(&'a str, &'a Value)>;
+
+/// Decodes the geometry (in tile units) and the properties of one feature
+fn decode_feature<'a>(feature: &[u8], keys: &'a [String], values: &'a [Value]) -> Result<(Vec<(i32, i32)>, Properties<'a>), Error> {
+    let mut geometry   = vec![];
+    let mut properties = vec![];
+    let mut fields     = Fields::new(feature);
+    while let Some((number, value)) = fields.next_field()? {
+        match number {
+            2 => {
+                let tags = packed(value.bytes()?)?;
+                for pair in tags.chunks(2) {
+                    let [key, value] = pair else {
+                        return Err(invalid("tags"));
+                    };
+                    let key   = keys.get(*key as usize).ok_or_else(|| invalid("tag key"))?;
+                    let value = values.get(*value as usize).ok_or_else(|| invalid("tag value"))?;
+                    properties.push((key.as_str(), value));
+                }
+            },
+            4 => geometry = decode_geometry(&packed(value.bytes()?)?)?,
+            _ => {}
+        }
+    }
+    Ok((geometry, properties))
+}
+
+/// Decodes the commands of a geometry into the points they visit. The parts of
+/// multi-geometries are chained, and closing a path adds no point.
+fn decode_geometry(commands: &[u64]) -> Result<Vec<(i32, i32)>, Error> {
+    let mut points = vec![];
+    let (mut x, mut y) = (0_i32, 0_i32);
+    let mut commands = commands.iter();
+    while let Some(command) = commands.next() {
+        let (id, count) = (command & 0x7, command >> 3);
+        match id {
+            // move to, line to
+            1 | 2 => for _ in 0..count {
+                let (Some(dx), Some(dy)) = (commands.next(), commands.next()) else {
+                    return Err(invalid("geometry"));
+                };
+                x = x.wrapping_add(zigzag(*dx) as i32);
+                y = y.wrapping_add(zigzag(*dy) as i32);
+                points.push((x, y));
+            },
+            // close path
+            7 => {},
+            _ => return Err(invalid("geometry command")),
+        }
+    }
+    Ok(points)
+}
+
+/// A value of a property
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    String(String),
+    Float(f64),
+    Int(i64),
+    Bool(bool),
+}
+impl Value {
+    fn as_f64(&self) -> f64 {
+        match self {
+            Value::Float(x) => *x,
+            Value::Int(x)   => *x as f64,
+            Value::Bool(x)  => f64::from(u8::from(*x)),
+            Value::String(x) => x.parse().unwrap_or_default(),
+        }
+    }
+    fn as_bool(&self) -> bool {
+        match self {
+            Value::Bool(x) => *x,
+            other => other.as_f64() != 0.0,
+        }
+    }
+    fn as_string(&self) -> String {
+        match self {
+            Value::String(x) => x.clone(),
+            Value::Float(x)  => x.to_string(),
+            Value::Int(x)    => x.to_string(),
+            Value::Bool(x)   => x.to_string(),
+        }
+    }
+}
+
+/// Decodes one value of the values table of a layer
+fn decode_value(value: &[u8]) -> Result<Value, Error> {
+    let mut decoded = Value::Int(0);
+    let mut fields  = Fields::new(value);
+    while let Some((number, value)) = fields.next_field()? {
+        decoded = match (number, value) {
+            (1, value)              => Value::String(value.string()?),
+            (2, Field::Fixed32(x))  => Value::Float(f64::from(f32::from_bits(x))),
+            (3, Field::Fixed64(x))  => Value::Float(f64::from_bits(x)),
+            (4, Field::Varint(x))   => Value::Int(x as i64),
+            (5, Field::Varint(x))   => Value::Int(x as i64),
+            (6, Field::Varint(x))   => Value::Int(zigzag(x)),
+            (7, Field::Varint(x))   => Value::Bool(x != 0),
+            _ => return Err(invalid("value")),
+        };
+    }
+    Ok(decoded)
+}
+
+/// The payload of one field of a protobuf message
+#[derive(Debug, Clone, Copy)]
+enum Field<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+impl <'a> Field<'a> {
+    fn varint(self) -> Result<u64, Error> {
+        match self {
+            Field::Varint(x) => Ok(x),
+            _ => Err(invalid("wire type")),
+        }
+    }
+    fn bytes(self) -> Result<&'a [u8], Error> {
+        match self {
+            Field::Bytes(x) => Ok(x),
+            _ => Err(invalid("wire type")),
+        }
+    }
+    fn string(self) -> Result<String, Error> {
+        String::from_utf8(self.bytes()?.to_vec()).map_err(|_| invalid("string"))
+    }
+}
+
+/// Iterates over the fields of a protobuf message
+struct Fields<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+impl <'a> Fields<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, position: 0 }
+    }
+    /// The number and payload of the next field (None at the end of the message)
+    fn next_field(&mut self) -> Result<Option<(u64, Field<'a>)>, Error> {
+        if self.position >= self.buffer.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let field = match key & 0x7 {
+            0 => Field::Varint(self.varint()?),
+            1 => Field::Fixed64(u64::from_le_bytes(self.take(8)?.try_into().expect("8 bytes"))),
+            2 => {
+                let length = usize::try_from(self.varint()?).map_err(|_| invalid("length"))?;
+                Field::Bytes(self.take(length)?)
+            },
+            5 => Field::Fixed32(u32::from_le_bytes(self.take(4)?.try_into().expect("4 bytes"))),
+            _ => return Err(invalid("wire type")),
+        };
+        Ok(Some((key >> 3, field)))
+    }
+    fn varint(&mut self) -> Result<u64, Error> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let byte = *self.buffer.get(self.position).ok_or_else(|| invalid("truncated varint"))?;
+            self.position += 1;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid("varint"))
+    }
+    fn take(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        let end = self.position.checked_add(length)
+            .filter(|end| *end <= self.buffer.len())
+            .ok_or_else(|| invalid("truncated field"))?;
+        let bytes = &self.buffer[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
+
+/// Decodes a packed repeated field of varints
+fn packed(bytes: &[u8]) -> Result<Vec<u64>, Error> {
+    let mut fields = Fields::new(bytes);
+    let mut values = vec![];
+    while fields.position < bytes.len() {
+        values.push(fields.varint()?);
+    }
+    Ok(values)
+}
+/// Decodes a zigzag encoded signed integer
+fn zigzag(value: u64) -> i64 {
+    ((value >> 1) as i64) ^ -((value & 1) as i64)
+}
+/// The error telling that the tile is malformed
+fn invalid(what: &str) -> Error {
+    Error::ParseError(format!("vector tile: invalid {what}"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DecodedTile, SpeedSegment, Turn};
+
+    fn varint(mut value: u64, out: &mut Vec<u8>) {
+        while value >= 0x80 {
+            out.push((value as u8 & 0x7F) | 0x80);
+            value >>= 7;
+        }
+        out.push(value as u8);
+    }
+    fn field(number: u64, payload: &[u8], out: &mut Vec<u8>) {
+        varint(number << 3 | 2, out);
+        varint(payload.len() as u64, out);
+        out.extend_from_slice(payload);
+    }
+    fn packed(values: &[u64]) -> Vec<u8> {
+        let mut out = vec![];
+        values.iter().for_each(|v| varint(*v, &mut out));
+        out
+    }
+    fn layer(name: &str, keys: &[&str], values: &[Vec<u8>], features: &[Vec<u8>]) -> Vec<u8> {
+        let mut out = vec![];
+        field(1, name.as_bytes(), &mut out);
+        features.iter().for_each(|f| field(2, f, &mut out));
+        keys.iter().for_each(|k| field(3, k.as_bytes(), &mut out));
+        values.iter().for_each(|v| field(4, v, &mut out));
+        varint(5 << 3, &mut out);
+        varint(4096, &mut out);
+        out
+    }
+    fn feature(tags: &[u64], geometry: &[u64]) -> Vec<u8> {
+        let mut out = vec![];
+        field(2, &packed(tags), &mut out);
+        field(4, &packed(geometry), &mut out);
+        out
+    }
+    fn string(value: &str) -> Vec<u8> {
+        let mut out = vec![];
+        field(1, value.as_bytes(), &mut out);
+        out
+    }
+    fn double(value: f64) -> Vec<u8> {
+        let mut out = vec![3 << 3 | 1];
+        out.extend_from_slice(&value.to_le_bytes());
+        out
+    }
+    fn uint(value: u64) -> Vec<u8> {
+        let mut out = vec![5 << 3];
+        varint(value, &mut out);
+        out
+    }
+    fn sint(value: i64) -> Vec<u8> {
+        let mut out = vec![6 << 3];
+        varint(((value << 1) ^ (value >> 63)) as u64, &mut out);
+        out
+    }
+    fn boolean(value: bool) -> Vec<u8> {
+        vec![7 << 3, u8::from(value)]
+    }
+
+    #[test]
+    fn speeds_and_turns_are_decoded() {
+        let speeds = layer("speeds",
+            &["speed", "is_small", "datasource", "duration", "weight", "name", "rate", "is_startpoint"],
+            &[uint(50), boolean(false), string("lua profile"), double(2.5), double(3.0), string("Rue Haute"), double(12.0), boolean(true)],
+            // move to (10, 20), line to (13, 16)
+            &[feature(&[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7], &[9, 20, 40, 10, 6, 7])]);
+        let turns = layer("turns",
+            &["bearing_in", "turn_angle", "cost", "weight", "type", "modifier"],
+            &[sint(-90), sint(45), double(1.5), double(-2.0), string("turn"), string("right")],
+            &[feature(&[0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5], &[9, 4, 6])]);
+        let mut tile = vec![];
+        field(3, &speeds, &mut tile);
+        field(3, &turns, &mut tile);
+        field(3, &layer("other", &[], &[], &[feature(&[], &[9, 0, 0])]), &mut tile);
+
+        let decoded = DecodedTile::decode(&tile).unwrap();
+        assert_eq!(4096, decoded.extent);
+        assert_eq!(vec![SpeedSegment {
+            geometry: vec![(10, 20), (13, 16)],
+            speed: 50,
+            is_small: false,
+            datasource: "lua profile".to_string(),
+            duration: 2.5,
+            weight: 3.0,
+            name: "Rue Haute".to_string(),
+            rate: 12.0,
+            is_startpoint: true,
+        }], decoded.speeds);
+        assert_eq!(vec![Turn {
+            location: (2, 3),
+            bearing_in: -90,
+            turn_angle: 45,
+            cost: 1.5,
+            weight: -2.0,
+            turn_type: "turn".to_string(),
+            modifier: "right".to_string(),
+        }], decoded.turns);
+    }
+    #[test]
+    fn empty_tile() {
+        assert_eq!(DecodedTile { extent: 4096, ..Default::default() }, DecodedTile::decode(&[]).unwrap());
+    }
+    #[test]
+    fn malformed_tiles_are_rejected() {
+        assert!(DecodedTile::decode(&[3 << 3 | 2, 10, 1]).is_err());
+        assert!(DecodedTile::decode(&[0xFF]).is_err());
+        assert!(DecodedTile::decode(b"<html>not found</html>").is_err());
+    }
+}
@@ -8,8 +8,106 @@ use serde::{Serialize, Deserialize};
 pub enum Error {
     #[error("http error {0}")]
     HttpError(#[from] reqwest::Error),
-    #[error("protocol error {0}")]
-    ProtocolError(OsrmStatus),
+    #[cfg(all(unix, feature = "unix-socket"))]
+    #[error("unix socket error {0}")]
+    SocketError(#[from] hyper::Error),
+    /// OSRM did not answer the request sent over the unix socket within the 
+    /// timeout of the client
+    #[cfg(all(unix, feature = "unix-socket"))]
+    #[error("no answer on the unix socket within {0:?}")]
+    SocketTimeout(std::time::Duration),
+    #[error("protocol error {status}{}", .message.as_ref().map(|m| format!(": {m}")).unwrap_or_default())]
+    ProtocolError { 
+        status: OsrmStatus, 
+        /// The human-readable explanation of the failure given by OSRM (if any)
+        message: Option<String> 
+    },
+    #[error("json error {0}")]
+    JsonError(#[from] serde_json::Error),
+    #[error("incomplete response: {0}")]
+    IncompleteResponse(String),
+    #[error("cannot decode response {source} (body: {body_snippet})")]
+    Decode { 
+        source: serde_json::Error, 
+        /// The beginning of the body which could not be decoded
+        body_snippet: String 
+    },
+    #[error("invalid polyline {0}")]
+    PolylineError(String),
+    #[error("invalid waypoints: {0}")]
+    InvalidWaypoints(String),
+    #[error("invalid timestamps: {0}")]
+    InvalidTimestamps(String),
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+    #[error("{actual} {option} given for {expected} coordinates")]
+    OptionLengthMismatch { option: &'static str, expected: usize, actual: usize },
+    #[error("missing {0} in the response")]
+    MissingData(&'static str),
+    #[error("{actual} {annotation} annotations given for {expected} segments")]
+    MisalignedAnnotation { annotation: &'static str, expected: usize, actual: usize },
+    #[error("cannot parse {0}")]
+    ParseError(String),
+}
+impl Error {
+    /// Gives access to the underlying reqwest error (if any) so as to let you
+    /// inspect the transport level diagnostics (e.g. `is_timeout()`, `status()`)
+    /// without resorting to string matching. 
+    pub fn as_reqwest(&self) -> Option<&reqwest::Error> {
+        match self {
+            Self::HttpError(e) => Some(e),
+            _ => None
+        }
+    }
+    /// Tells whether sending the very same request again might succeed. This is
+    /// the case of transient transport failures (timeouts, connection errors,
+    /// throttling or server errors, responses truncated by a dropped connection)
+    /// and of the retryable protocol errors (see `OsrmStatus::is_retryable`).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::HttpError(e) => e.is_timeout() || e.is_connect() || e.is_body() ||
+                e.status().is_some_and(|s| s.as_u16() == 429 || s.is_server_error()),
+            #[cfg(all(unix, feature = "unix-socket"))]
+            Self::SocketError(e) => e.is_incomplete_message(),
+            Self::ProtocolError { status, .. } => status.is_retryable(),
+            Self::IncompleteResponse(_) => true,
+            #[cfg(all(unix, feature = "unix-socket"))]
+            Self::SocketTimeout(_) => true,
+            _ => false
+        }
+    }
+    /// The http status a web service relaying this error should answer with 
+    /// (feature `http-status`). The protocol errors map to their conventional 
+    /// status (see `OsrmStatus::http_equivalent`) and the invalid requests to
+    /// 400 (bad request). The failures to reach OSRM or to make sense of its 
+    /// response map to 502 (bad gateway), or 504 (gateway timeout) when OSRM 
+    /// did not answer in time.
+    #[cfg(feature = "http-status")]
+    pub fn http_status(&self) -> http::StatusCode {
+        use http::StatusCode;
+        match self {
+            Self::ProtocolError { status, .. } => StatusCode::from_u16(status.http_equivalent())
+                .unwrap_or(StatusCode::BAD_REQUEST),
+            Self::HttpError(e) if e.is_timeout() => StatusCode::GATEWAY_TIMEOUT,
+            #[cfg(all(unix, feature = "unix-socket"))]
+            Self::SocketTimeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::PolylineError(_)
+            | Self::InvalidWaypoints(_)
+            | Self::InvalidTimestamps(_)
+            | Self::InvalidRequest(_)
+            | Self::OptionLengthMismatch { .. }
+            | Self::ParseError(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+/// Lets web services answer with an error directly (e.g. `(StatusCode, String)` 
+/// implements `IntoResponse` in axum). The message is the description of the error.
+#[cfg(feature = "http-status")]
+impl From<Error> for (http::StatusCode, String) {
+    fn from(error: Error) -> Self {
+        (error.http_status(), error.to_string())
+    }
 }
 
 
@@ -55,4 +153,98 @@ pub enum OsrmStatus {
     // Emitted by the trip service
     #[display("No trips found because input coordinates are not connected.")]
     NoTrips,
-}
\ No newline at end of file
+}
+impl OsrmStatus {
+    /// Tells whether a request that failed with this status could succeed if it 
+    /// were sent again as is. All the codes documented by OSRM are permanent: 
+    /// they either denote a problem with the request itself (e.g. `InvalidOptions`,
+    /// `TooBig`) or with the data it bears on (e.g. `NoSegment`, `NoRoute`), and 
+    /// retrying will not change the outcome. A retry policy should hence only 
+    /// consider transport level failures (see `Error::is_retryable`).
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::Ok             => false,
+            Self::InvalidUrl     => false,
+            Self::InvalidService => false,
+            Self::InvalidVersion => false,
+            Self::InvalidOptions => false,
+            Self::InvalidQuery   => false,
+            Self::InvalidValue   => false,
+            Self::NoSegment      => false,
+            Self::TooBig         => false,
+            Self::NotImplemented => false,
+            Self::NoRoute        => false,
+            Self::NoTable        => false,
+            Self::NoMatch        => false,
+            Self::NoTrips        => false,
+        }
+    }
+    /// The http status conventionally associated with this code. This mirrors
+    /// the statuses `osrm-routed` answers with: 200 for `Ok` and 400 (bad 
+    /// request) for the errors, be they due to the request itself (e.g. 
+    /// `InvalidOptions`, `TooBig`) or to the data it bears on (e.g. `NoSegment`,
+    /// `NoRoute`). The one exception is `NotImplemented` which maps to 501. 
+    /// This is meant for services relaying OSRM errors in their own http responses.
+    pub fn http_equivalent(&self) -> u16 {
+        match self {
+            Self::Ok             => 200,
+            Self::InvalidUrl     => 400,
+            Self::InvalidService => 400,
+            Self::InvalidVersion => 400,
+            Self::InvalidOptions => 400,
+            Self::InvalidQuery   => 400,
+            Self::InvalidValue   => 400,
+            Self::NoSegment      => 400,
+            Self::TooBig         => 400,
+            Self::NotImplemented => 501,
+            Self::NoRoute        => 400,
+            Self::NoTable        => 400,
+            Self::NoMatch        => 400,
+            Self::NoTrips        => 400,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{Error, OsrmStatus};
+
+    #[test]
+    fn protocol_errors_are_not_retryable() {
+        assert!(!OsrmStatus::NoSegment.is_retryable());
+        assert!(!OsrmStatus::NoRoute.is_retryable());
+        assert!(!OsrmStatus::TooBig.is_retryable());
+        assert!(!Error::ProtocolError { status: OsrmStatus::NoRoute, message: None }.is_retryable());
+        assert!(!Error::MissingData("durations").is_retryable());
+        assert!(Error::IncompleteResponse("eof".to_string()).is_retryable());
+    }
+    #[test]
+    fn protocol_errors_display_the_message() {
+        let error = Error::ProtocolError { status: OsrmStatus::NoRoute, message: Some("Impossible route between points".to_string()) };
+        assert_eq!("protocol error no route found: Impossible route between points", error.to_string());
+        let error = Error::ProtocolError { status: OsrmStatus::NoRoute, message: None };
+        assert_eq!("protocol error no route found", error.to_string());
+    }
+    #[cfg(feature = "http-status")]
+    #[test]
+    fn errors_convert_to_http_status_and_message() {
+        use http::StatusCode;
+        let (status, message) = Error::ProtocolError { status: OsrmStatus::NoRoute, message: None }.into();
+        assert_eq!(StatusCode::BAD_REQUEST, status);
+        assert_eq!("protocol error no route found", message);
+
+        assert_eq!(StatusCode::NOT_IMPLEMENTED, Error::ProtocolError { status: OsrmStatus::NotImplemented, message: None }.http_status());
+        assert_eq!(StatusCode::BAD_REQUEST, Error::InvalidRequest("nope".to_string()).http_status());
+        assert_eq!(StatusCode::BAD_GATEWAY, Error::MissingData("routes").http_status());
+        assert_eq!(StatusCode::BAD_GATEWAY, Error::IncompleteResponse("eof".to_string()).http_status());
+    }
+    #[test]
+    fn http_equivalent_of_the_codes() {
+        assert_eq!(200, OsrmStatus::Ok.http_equivalent());
+        assert_eq!(400, OsrmStatus::InvalidOptions.http_equivalent());
+        assert_eq!(400, OsrmStatus::TooBig.http_equivalent());
+        assert_eq!(400, OsrmStatus::NoSegment.http_equivalent());
+        assert_eq!(400, OsrmStatus::NoRoute.http_equivalent());
+        assert_eq!(501, OsrmStatus::NotImplemented.http_equivalent());
+    }
+}
@@ -1,15 +1,20 @@
 //! This module defines the base functionalities for the ORSM client.
 //! These functionalities are common to all services offered through the API.
 
-use std::fmt::Display;
-use serde::Deserialize;
+use std::{borrow::Cow, fmt::Display, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant}};
+use bytes::Bytes;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, HOST, CONTENT_LENGTH};
+use serde::{Deserialize, de::DeserializeOwned};
 
-use crate::{OsrmStatus, Error};
+use crate::{OsrmStatus, Error, Coordinates, Service, TransportationMode, encode_polyline};
 
 /// The default URL to use as base for the interaction with OSRM
 const OSRM_BASE: &str = "http://router.project-osrm.org";
 /// The default version of the API which is used
 const OSRM_VERSION: &str = "v1";
+/// The maximum number of requests sent simultaneously by the bulk methods of 
+/// the client (e.g. `Client::tiles_for_bbox` or `Client::nearest_many`)
+pub(crate) const MAX_CONCURRENT_REQUESTS: usize = 4;
 
 /// This is the client you will use to connect to the HTTP service of your 
 /// ORSM backend. 
@@ -21,8 +26,43 @@ pub struct Client {
     /// This is the base URL of the OSRM instance you will connect to. By
     /// default, this value is going to be "http://router.project-osrm.org";
     pub(crate) base_url: String,
+    /// The path under which the services are mounted, between the base url and
+    /// the name of the service (see [`Client::service_prefix`]). Empty by default.
+    pub(crate) service_prefix: String,
     /// The version of the API (so far, only v1 is supported)
     pub(crate) version: String,
+    /// The mode of transportation used by the requests which do not specify 
+    /// their own profile (see [`Client::default_profile`])
+    pub(crate) default_profile: TransportationMode,
+    /// When true, a request failing with `NoSegment` under the default snapping
+    /// is retried once with `snapping=any` (see [`Client::snapping_fallback`])
+    pub(crate) snapping_fallback: bool,
+    /// When set, requests bearing on more coordinates than this threshold encode
+    /// them as a polyline (see [`Client::auto_polyline_threshold`])
+    pub(crate) auto_polyline_threshold: Option<usize>,
+    /// When set, requests whose url would be longer than this threshold are sent
+    /// with POST (see [`Client::post_threshold`])
+    pub(crate) post_threshold: Option<usize>,
+    /// The headers which are sent along with every request
+    pub(crate) headers: HeaderMap,
+    /// The maximum duration of each request (see [`Client::with_timeout`])
+    pub(crate) timeout: Option<Duration>,
+    /// How the requests failing with a transient error are sent again (see 
+    /// [`Client::retry_policy`])
+    pub(crate) retry_policy: crate::RetryPolicy,
+    /// When true, the options of each request are logged (see [`Client::log_requests`])
+    pub(crate) log_requests: bool,
+    /// The radius applied to all the coordinates of the requests which do not
+    /// specify their `radiuses` (see [`Client::default_radius`])
+    pub(crate) default_radius: Option<crate::Radius>,
+    /// When set, the requests are sent over this unix socket rather than tcp
+    #[cfg(all(unix, feature = "unix-socket"))]
+    pub(crate) unix_socket: Option<crate::UnixSocket>,
+    /// When set, the successful responses are kept in this cache (see [`Client::with_cache`])
+    #[cfg(feature = "cache")]
+    pub(crate) cache: Option<crate::ResponseCache>,
+    /// The counters tracking the outcome of the requests (shared among clones)
+    pub(crate) counters: Arc<Counters>,
 }
 
 impl Default for Client {
@@ -30,14 +70,40 @@ impl Default for Client {
         Self { 
             reqwest:  Default::default(), 
             base_url: OSRM_BASE.to_string(),
+            service_prefix: String::new(),
             version:  OSRM_VERSION.to_string(),
+            default_profile: TransportationMode::Car,
+            snapping_fallback: false,
+            auto_polyline_threshold: None,
+            post_threshold: None,
+            headers:  HeaderMap::new(),
+            timeout:  None,
+            retry_policy: Default::default(),
+            log_requests: false,
+            default_radius: None,
+            #[cfg(all(unix, feature = "unix-socket"))]
+            unix_socket: None,
+            #[cfg(feature = "cache")]
+            cache: None,
+            counters: Default::default(),
         }
     }
 }
 impl Client {
-    /// Sets the base url of the client
+    /// Sets the base url of the client. Its trailing slashes (if any) are 
+    /// irrelevant: `http://localhost:5000/` and `http://localhost:5000` are the same.
     pub fn base_url(mut self, url: String) -> Self {
-        self.base_url = url;
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+    /// Sets the path under which the services are mounted, for the OSRM instances
+    /// sitting behind a reverse proxy which exposes them under some prefix (e.g.
+    /// with the prefix `osrm`, the route service is reached at `{base}/osrm/route/v1/...`).
+    /// The leading and trailing slashes of the prefix are irrelevant. This applies 
+    /// to all services, and no prefix is used by default.
+    pub fn service_prefix(mut self, prefix: &str) -> Self {
+        let prefix = prefix.trim_matches('/');
+        self.service_prefix = if prefix.is_empty() { String::new() } else { format!("/{prefix}") };
         self
     }
     /// Sets the protocol version (currently useless since v1 is the only supported version)
@@ -45,24 +111,277 @@ impl Client {
         self.version = version;
         self
     }
+    /// Sets the mode of transportation used by the requests which leave their
+    /// `profile` unset (`Car` unless specified otherwise). The profile set on a
+    /// request always takes precedence over this default.
+    pub fn default_profile(mut self, profile: TransportationMode) -> Self {
+        self.default_profile = profile;
+        self
+    }
+    /// When enabled, any request that fails with a `NoSegment` error while using
+    /// the default snapping is retried once with `snapping=any`. This rescues 
+    /// coordinates located on service roads or parking lots which are excluded 
+    /// by the default `is_startpoint` filtering. 
+    /// 
+    /// Beware: `any` may snap to edges that make for lower quality routes.
+    pub fn snapping_fallback(mut self, enabled: bool) -> Self {
+        self.snapping_fallback = enabled;
+        self
+    }
+    /// Sets the radius which limits the snapping of the coordinates of all the 
+    /// requests which leave their `radiuses` unset. That radius is broadcast to
+    /// all the coordinates of the request (i.e. it is repeated once per coordinate).
+    /// The `radiuses` set on a request always take precedence over this default.
+    /// This is typically useful to cope with consistently noisy gps traces.
+    pub fn default_radius(mut self, radius: crate::Radius) -> Self {
+        self.default_radius = Some(radius);
+        self
+    }
+    /// When a request bears on a sequence of more than `n` coordinates, these
+    /// are transparently encoded as a `polyline6` in the url so as to stay 
+    /// under the url length limits. This is off by default for predictability.
+    pub fn auto_polyline_threshold(mut self, n: usize) -> Self {
+        self.auto_polyline_threshold = Some(n);
+        self
+    }
+    /// When the url of a request (query string included) would be longer than 
+    /// `n` characters, the request is sent with POST instead of GET: its 
+    /// coordinates and options then travel as a form in the body of the request
+    /// (e.g. `coordinates=4.35,50.85;4.4,50.8&steps=true`) rather than in the 
+    /// url. This avoids the `414 URI Too Long` answers to the requests bearing
+    /// on many coordinates (e.g. large tables), provided that the backend (or
+    /// the gateway in front of it) accepts POST requests. This is off by default.
+    /// 
+    /// Requests sent over a unix socket always use GET.
+    pub fn post_threshold(mut self, n: usize) -> Self {
+        self.post_threshold = Some(n);
+        self
+    }
+    /// Adds the given headers to the ones sent along with every request. This is
+    /// typically useful when OSRM sits behind a gateway that requires some custom
+    /// headers (e.g. an api key). 
+    /// 
+    /// The `Host` and `Content-Length` headers are reserved: they are computed 
+    /// for each request and hence silently ignored when present in `headers`.
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        for (name, value) in headers.iter() {
+            self = self.header(name.clone(), value.clone());
+        }
+        self
+    }
+    /// Adds one header to the ones sent along with every request. Setting the 
+    /// same header twice replaces its previous value. 
+    /// 
+    /// The `Host` and `Content-Length` headers are reserved: they are computed
+    /// for each request and hence silently ignored.
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        if name != HOST && name != CONTENT_LENGTH {
+            self.headers.insert(name, value);
+        }
+        self
+    }
+    /// Sets the maximum duration of every request sent by this client (tiles 
+    /// included), from the connection until the end of the response body. A 
+    /// request taking longer fails with an `HttpError` whose underlying error
+    /// `is_timeout()` (which is retryable, see `Client::retry_policy`). There
+    /// is no timeout by default: a hung backend would be waited for forever.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// Sets how the requests failing with a transient error (e.g. 429 or 502 
+    /// answers of an overloaded server) are sent again, with an exponential 
+    /// backoff (see `RetryPolicy`). No request is retried by default.
+    pub fn retry_policy(mut self, policy: crate::RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+    /// When enabled, the service, number of coordinates and options of each 
+    /// request are logged at debug level (through the `log` crate). This shows 
+    /// which options are actually sent without exposing the coordinates which
+    /// are redacted to their count. This is off by default.
+    pub fn log_requests(mut self, enabled: bool) -> Self {
+        self.log_requests = enabled;
+        self
+    }
+    /// A snapshot of the metrics about the requests sent by this client (and all
+    /// of its clones) so far.
+    pub fn metrics(&self) -> ClientMetrics {
+        ClientMetrics {
+            requests:        self.counters.requests.load(Ordering::Relaxed),
+            successes:       self.counters.successes.load(Ordering::Relaxed),
+            protocol_errors: self.counters.protocol_errors.load(Ordering::Relaxed),
+            http_errors:     self.counters.http_errors.load(Ordering::Relaxed),
+            total_latency:   Duration::from_micros(self.counters.latency_micros.load(Ordering::Relaxed)),
+        }
+    }
+    /// Updates the metrics with the outcome of one request which took `elapsed` to complete
+    pub(crate) fn record<T>(&self, outcome: &Result<T, Error>, elapsed: Duration) {
+        let counter = match outcome {
+            Ok(_)                        => &self.counters.successes,
+            Err(Error::ProtocolError { .. }) => &self.counters.protocol_errors,
+            Err(_)                       => &self.counters.http_errors,
+        };
+        self.counters.requests.fetch_add(1, Ordering::Relaxed);
+        self.counters.latency_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Sends a GET request to the given url (along with the default headers and
+    /// the given query options) and returns the headers and body of the response.
+    /// The request is posted instead when its url exceeds the `post_threshold`.
+    /// The responses whose status is not a success fail (see `check_status`).
+    pub(crate) async fn fetch(&self, url: &str, options: &[(&'static str, String)]) -> Result<(HeaderMap, Bytes), Error> {
+        #[cfg(all(unix, feature = "unix-socket"))]
+        if let Some(socket) = &self.unix_socket {
+            return socket.fetch(url, &self.headers, options, self.timeout).await;
+        }
+        let mut request = match self.post_form(url, options) {
+            Some((url, form)) => self.reqwest.post(url).form(&form),
+            None => self.reqwest.get(url).query(options),
+        }.headers(self.headers.clone());
+        if let Some(timeout) = self.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send().await?;
+        let status   = response.error_for_status_ref().map(|_| ());
+        let headers  = response.headers().clone();
+        let body     = response.bytes().await?;
+        self.check_status(status, &body)?;
+        check_complete(&headers, &body)?;
+        Ok((headers, body))
+    }
+}
+impl Client {
+    /// Fails when the status of the response (as checked by `error_for_status_ref`)
+    /// is not a success: with a `ProtocolError` when its body is an OSRM error 
+    /// (OSRM answers its protocol errors with a 400), and with an `HttpError` 
+    /// otherwise. The transient statuses of the retry policy always fail with an 
+    /// `HttpError`, so that they are retried.
+    pub(crate) fn check_status(&self, status: Result<(), reqwest::Error>, body: &[u8]) -> Result<(), Error> {
+        let error = match status {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        let transient = error.status().is_some_and(|s| self.retry_policy.is_retriable_status(s.as_u16()));
+        match serde_json::from_slice::<Status>(body) {
+            Ok(Status { code, message }) if !transient && !matches!(code, OsrmStatus::Ok) => 
+                Err(Error::ProtocolError { status: code, message }),
+            _ => Err(error.into())
+        }
+    }
+    /// The url and form a request must be posted with when its url would exceed
+    /// the `post_threshold` of the client (None when it must be sent with GET). 
+    /// The coordinates, which are the last segment of the url, move to the form.
+    pub(crate) fn post_form<'a>(&self, url: &'a str, options: &[(&'static str, String)]) -> Option<(&'a str, Vec<(&'static str, String)>)> {
+        let threshold = self.post_threshold?;
+        let length    = reqwest::Url::parse_with_params(url, options).ok()?.as_str().len();
+        if length <= threshold {
+            return None;
+        }
+        let (path, coordinates) = url.rsplit_once('/')?;
+        let mut form = vec![("coordinates", coordinates.to_string())];
+        form.extend(options.iter().cloned());
+        Some((path, form))
+    }
+}
+/// Checks that the body is not shorter than announced by the `Content-Length`
+/// header, which is what happens when the connection drops mid-body
+pub(crate) fn check_complete(headers: &HeaderMap, body: &Bytes) -> Result<(), Error> {
+    let expected = headers.get(CONTENT_LENGTH)
+        .and_then(|length| length.to_str().ok())
+        .and_then(|length| length.parse::<usize>().ok());
+    match expected {
+        Some(expected) if body.len() < expected => 
+            Err(Error::IncompleteResponse(format!("received {} of the {expected} bytes of the body", body.len()))),
+        _ => Ok(())
+    }
 }
+/// The counters tracking the outcome of the requests sent by a client
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    requests:        AtomicU64,
+    successes:       AtomicU64,
+    protocol_errors: AtomicU64,
+    http_errors:     AtomicU64,
+    latency_micros:  AtomicU64,
+}
+
+/// Metrics about the requests sent by a client (see `Client::metrics`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ClientMetrics {
+    /// The total number of requests sent
+    pub requests: u64,
+    /// The number of requests that completed successfully
+    pub successes: u64,
+    /// The number of requests that were rejected by OSRM with a protocol error
+    pub protocol_errors: u64,
+    /// The number of requests that failed at the http level (including the 
+    /// failure to decode the body of the response)
+    pub http_errors: u64,
+    /// The cumulated wall time of all these requests. Dividing it by the number
+    /// of `requests` gives the mean latency; the latency of individual requests
+    /// is available through `send_timed`.
+    pub total_latency: Duration,
+}
+
+impl Coordinates {
+    /// The coordinates as they should be formatted in the url of a request sent 
+    /// by the given client (possibly encoded as a polyline)
+    pub(crate) fn for_url(&self, client: &Client) -> Cow<'_, Coordinates> {
+        match (self, client.auto_polyline_threshold) {
+            (Coordinates::Multi(locations), Some(n)) if locations.len() > n => 
+                Cow::Owned(Coordinates::Polyline6(encode_polyline(locations, 6))),
+            _ => Cow::Borrowed(self)
+        }
+    }
+}
+
 pub trait Request : WithOptions {}
 pub trait WithOptions {
     fn options(&self) -> Vec<(&'static str, String)>;
+    /// Checks the consistency of the service specific options before the 
+    /// request is sent. By default, all options are deemed valid.
+    fn validate(&self) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
+/// The types a request may hold its coordinates in
+pub(crate) trait AsCoordinates {
+    /// The coordinates of the request
+    fn as_coordinates(&self) -> Cow<'_, Coordinates>;
+}
+impl AsCoordinates for Coordinates {
+    fn as_coordinates(&self) -> Cow<'_, Coordinates> {
+        Cow::Borrowed(self)
+    }
+}
+impl AsCoordinates for crate::SingleCoordinate {
+    fn as_coordinates(&self) -> Cow<'_, Coordinates> {
+        Cow::Owned(Coordinates::Single(self.location()))
+    }
+}
+
+/// Declares a request (along with its builder) bearing the general options. 
+/// When the name of the request is preceded by `#[validate]`, its builder must
+/// define a `fn validate(&self) -> Result<(), String>` which `build` calls.
 macro_rules! request {
-    ($name:ident ($service:expr) -> $response:ty { $( $(#[$att:meta])* $fi:ident : $ft:ty),* }) => {
+    ($(#[$validate:ident])? $name:ident ($service:expr) -> $response:ty { $( $(#[$att:meta])* $fi:ident : $ft:ty),* }) => {
+        crate::request!($(#[$validate])? $name ($service, crate::Coordinates) -> $response { $( $(#[$att])* $fi : $ft),* });
+    };
+    ($(#[$validate:ident])? $name:ident ($service:expr, $coordinates:ty) -> $response:ty { $( $(#[$att:meta])* $fi:ident : $ft:ty),* }) => {
         #[derive(Debug, Clone, derive_builder::Builder)]
+        $(#[builder(build_fn($validate = "Self::validate"))])?
         pub struct $name {
             // -------------------------------------------------------
             // ---  STUFFS THAT ARE COMMON TO ALL REQUESTS -----------
             // -------------------------------------------------------
-            /// Mode of transportation
-            #[builder(default="crate::TransportationMode::Car")]
-            profile: crate::TransportationMode,
+            /// Mode of transportation (defaults to the `default_profile` of the client)
+            #[builder(default, setter(into, strip_option))]
+            profile: Option<crate::TransportationMode>,
             /// Coordinates the request bears on
-            coordinates: crate::Coordinates,
+            #[builder(setter(into))]
+            coordinates: $coordinates,
             // -------------------------------------------------------
             // ---  GENERAL OPTIONS ----------------------------------
             // -------------------------------------------------------
@@ -92,6 +411,14 @@ macro_rules! request {
             #[builder(default="false")]
             skip_waypoints: bool,
             // -------------------------------------------------------
+            // ---  CLIENT SIDE BEHAVIOR -----------------------------
+            // -------------------------------------------------------
+            /// Retry once with `snapping=any` when the request fails with `NoSegment` 
+            /// under the default snapping (see `Client::snapping_fallback`). Beware:
+            /// `any` may snap to edges that make for lower quality routes.
+            #[builder(default="false")]
+            snapping_fallback: bool,
+            // -------------------------------------------------------
             // ---  SERVICE SPECIFIC OPTIONS -------------------------
             // -------------------------------------------------------
             $( $(#[$att])* $fi : $ft),*
@@ -101,48 +428,121 @@ macro_rules! request {
 
         impl $name {
             pub async fn send(&self, client: &crate::Client) -> Result<$response, crate::Error> {
+                let (rsp, _headers) = self.send_with_headers(client).await?;
+                Ok(rsp)
+            }
+            /// Sends the request and returns the parsed response along with the 
+            /// http headers of the response (e.g. rate-limit headers set by a gateway)
+            pub async fn send_with_headers(&self, client: &crate::Client) -> Result<($response, reqwest::header::HeaderMap), crate::Error> {
+                crate::WithOptions::validate(self)?;
+                self.validate_general_options()?;
+
                 let mut options = self.options();
-                self.add_general_options(&mut options);
-
-                client.reqwest.get(self.url(client))
-                    .query(&options)
-                    .send()
-                    .await?
-                    .json::<crate::Response<$response>>()
-                    .await?
-                    .into()
+                self.add_general_options(client, &mut options);
+
+                let url = self.url(client);
+                let coordinates = crate::AsCoordinates::as_coordinates(&self.coordinates);
+                match crate::execute::<$response>(client, $service, &coordinates, &url, &options).await {
+                    Err(crate::Error::ProtocolError { status: crate::OsrmStatus::NoSegment, .. }) if self.should_fallback(client) => {
+                        crate::set_option(&mut options, "snapping", crate::Snapping::Any);
+                        crate::execute::<$response>(client, $service, &coordinates, &url, &options).await
+                    },
+                    rsp => rsp
+                }
+            }
+            /// Sends the request and returns the parsed response along with the 
+            /// wall time it took, including the retries and the snapping fallback
+            /// (see also `ClientMetrics::total_latency`)
+            pub async fn send_timed(&self, client: &crate::Client) -> Result<($response, std::time::Duration), crate::Error> {
+                let start = std::time::Instant::now();
+                let rsp   = self.send(client).await?;
+                Ok((rsp, start.elapsed()))
             }
             pub async fn debug(&self, client: &crate::Client) -> Result<String, crate::Error> {
                 let mut options = self.options();
-                self.add_general_options(&mut options);
-
-                let rsp = client.reqwest.get(self.url(client))
-                    .query(&options)
-                    .send()
-                    .await?
-                    .text()
-                    .await?;
-                    
-                Ok(rsp)
+                self.add_general_options(client, &mut options);
+
+                let (_headers, body) = client.fetch(&self.url(client), &options).await?;
+                Ok(String::from_utf8_lossy(&body).into_owned())
+            }
+            /// Sends the request and waits for its parsed response, blocking the
+            /// current thread (feature `blocking`)
+            #[cfg(feature = "blocking")]
+            pub fn send_blocking(&self, client: &crate::BlockingClient) -> Result<$response, crate::Error> {
+                crate::WithOptions::validate(self)?;
+                self.validate_general_options()?;
+
+                let config = client.config();
+                let mut options = self.options();
+                self.add_general_options(config, &mut options);
+
+                let url = self.url(config);
+                let coordinates = crate::AsCoordinates::as_coordinates(&self.coordinates);
+                let outcome = match crate::execute_blocking::<$response>(client, $service, &coordinates, &url, &options) {
+                    Err(crate::Error::ProtocolError { status: crate::OsrmStatus::NoSegment, .. }) if self.should_fallback(config) => {
+                        crate::set_option(&mut options, "snapping", crate::Snapping::Any);
+                        crate::execute_blocking::<$response>(client, $service, &coordinates, &url, &options)
+                    },
+                    rsp => rsp
+                };
+                outcome.map(|(rsp, _headers)| rsp)
+            }
+            /// Same as `debug`, but blocks the current thread (feature `blocking`)
+            #[cfg(feature = "blocking")]
+            pub fn debug_blocking(&self, client: &crate::BlockingClient) -> Result<String, crate::Error> {
+                let config = client.config();
+                let mut options = self.options();
+                self.add_general_options(config, &mut options);
+
+                let (_headers, body) = client.fetch(&self.url(config), &options)?;
+                Ok(String::from_utf8_lossy(&body).into_owned())
             }
 
             fn url(&self, client: &crate::Client) -> String {
                 let base    = &client.base_url;
+                let prefix  = &client.service_prefix;
                 let version = &client.version;
                 let service = $service;
-                let profile = self.profile;
-                let coord   = &self.coordinates;
+                let profile = self.profile.unwrap_or(client.default_profile).as_str();
+                let coords  = crate::AsCoordinates::as_coordinates(&self.coordinates);
+                let coord   = coords.for_url(client);
+
+                format!("{base}{prefix}/{service}/{version}/{profile}/{coord}")
+            }
+
+            /// True iff a `NoSegment` failure should be retried with `snapping=any`
+            fn should_fallback(&self, client: &crate::Client) -> bool {
+                let default_snapping = matches!(self.snapping, None | Some(crate::Snapping::Default));
+                default_snapping && (self.snapping_fallback || client.snapping_fallback)
+            }
 
-                format!("{base}/{service}/{version}/{profile}/{coord}")
+            /// Checks the consistency of the general options with the coordinates:
+            /// the per-coordinate options (`bearings`, `radiuses`, `hints` and 
+            /// `approaches`) must comprise exactly one value per coordinate. 
+            /// OSRM would reject the request otherwise. This check is performed
+            /// before the request is sent; it fails with `OptionLengthMismatch`.
+            pub fn validate_general_options(&self) -> Result<(), crate::Error> {
+                let coordinates = crate::AsCoordinates::as_coordinates(&self.coordinates);
+                crate::validate_length("bearings",   self.bearings.as_ref(),   &coordinates)?;
+                crate::validate_length("radiuses",   self.radiuses.as_ref(),   &coordinates)?;
+                crate::validate_length("hints",      self.hints.as_ref(),      &coordinates)?;
+                crate::validate_length("approaches", self.approaches.as_ref(), &coordinates)
             }
 
-            fn add_general_options(&self, options: &mut Vec<(&'static str, String)>) {
+            fn add_general_options(&self, client: &crate::Client, options: &mut Vec<(&'static str, String)>) {
                 crate::add_option!(opt multi options, bearings,       self.bearings);
                 crate::add_option!(opt multi options, radiuses,       self.radiuses);
+                if let (None, Some(radius)) = (&self.radiuses, client.default_radius) {
+                    let coordinates = crate::AsCoordinates::as_coordinates(&self.coordinates);
+                    crate::add_option!(opt multi options, radiuses, coordinates.count().ok().map(|n| vec![radius; n]));
+                }
                 crate::add_option!(          options, generate_hints, self.generate_hints);
                 crate::add_option!(opt multi options, hints,          self.hints);
                 crate::add_option!(opt multi options, approaches,     self.approaches);
-                crate::add_option!(opt multi options, exclude,        self.exclude);
+                // unlike the other lists, the excluded classes are comma separated
+                if let Some(exclude) = self.exclude.as_ref() {
+                    options.push(("exclude", exclude.join(",")));
+                }
                 crate::add_option!(opt       options, snapping,       self.snapping);
                 crate::add_option!(          options, skip_waypoints, self.skip_waypoints);
             }
@@ -184,11 +584,187 @@ impl <T> From<Response<T>> for Result<T, Error> {
     fn from(value: Response<T>) -> Self {
         match value.code {
             OsrmStatus::Ok => Ok(value.data),
-            _ => Err(Error::ProtocolError(value.code))
+            status => Err(Error::ProtocolError { status, message: value.message })
         }
     }
 }
 
+/// Sends a GET request to the given url and interprets the OSRM response.
+/// The http headers of the response are returned along with the response itself.
+/// Requests failing with a transient error are sent again as per the retry policy
+/// of the client. When the client has a cache, the requests it holds a fresh 
+/// response to are not sent.
+pub(crate) async fn execute<T: DeserializeOwned>(client: &Client, service: Service, coordinates: &Coordinates, url: &str, options: &[(&'static str, String)]) -> Result<(T, HeaderMap), Error> {
+    #[cfg(feature = "cache")]
+    if let Some(outcome) = from_cache(client, url, options) {
+        return outcome;
+    }
+    log_request(client, service, coordinates, options);
+    crate::retrying(client, || execute_once(client, url, options)).await
+}
+/// Sends the request once and parses its response
+async fn execute_once<T: DeserializeOwned>(client: &Client, url: &str, options: &[(&'static str, String)]) -> Result<(T, HeaderMap), Error> {
+    let start   = Instant::now();
+    let outcome = match client.fetch(url, options).await {
+        Ok((headers, body)) => parse(client, url, options, headers, body),
+        Err(error) => Err(error),
+    };
+    client.record(&outcome, start.elapsed());
+    outcome
+}
+/// Interprets the OSRM response to the given request (and caches it if the 
+/// client has a cache and the request succeeded)
+#[cfg_attr(not(feature = "cache"), allow(unused_variables))]
+pub(crate) fn parse<T: DeserializeOwned>(client: &Client, url: &str, options: &[(&'static str, String)], headers: HeaderMap, body: Bytes) -> Result<(T, HeaderMap), Error> {
+    let response = decode_response::<T>(&String::from_utf8_lossy(&body))?;
+    #[cfg(feature = "cache")]
+    if let (Some(cache), OsrmStatus::Ok) = (&client.cache, response.code) {
+        cache.insert(url, options, response.data_version.as_deref(), headers.clone(), body.clone());
+    }
+    let response: Result<T, Error> = response.into();
+    Ok((response?, headers))
+}
+/// The response to the given request, when the cache of the client holds a fresh one
+#[cfg(feature = "cache")]
+pub(crate) fn from_cache<T: DeserializeOwned>(client: &Client, url: &str, options: &[(&'static str, String)]) -> Option<Result<(T, HeaderMap), Error>> {
+    let (headers, body) = client.cache.as_ref()?.get(url, options)?;
+    let outcome = decode_response::<T>(&String::from_utf8_lossy(&body))
+        .and_then(|response| response.into())
+        .map(|response| (response, headers));
+    Some(outcome)
+}
+
+/// Logs (at debug level) the service, the number of coordinates and the options
+/// of a request when the client is configured to do so. The coordinates 
+/// themselves are never logged as these might be sensitive.
+pub(crate) fn log_request(client: &Client, service: Service, coordinates: &Coordinates, options: &[(&'static str, String)]) {
+    if client.log_requests {
+        log::debug!("{}", request_summary(service, coordinates, options));
+    }
+}
+/// A summary of the request where the coordinates are redacted to their count
+fn request_summary(service: Service, coordinates: &Coordinates, options: &[(&'static str, String)]) -> String {
+    let count = coordinates.count().map_or_else(|_| "?".to_string(), |n| n.to_string());
+    let options = options.iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("osrm {service} request with {count} coordinates: {options}")
+}
+
+/// The maximum number of characters of the body kept in a `Decode` error
+const BODY_SNIPPET_LEN: usize = 256;
+
+/// Deserializes the body of a response. Upon failure, the beginning of the 
+/// body is kept in the error so as to ease the diagnosis of type mismatches.
+/// A body ending prematurely yields an `IncompleteResponse` instead.
+pub(crate) fn decode<T: DeserializeOwned>(body: &str) -> Result<T, Error> {
+    serde_json::from_str(body).map_err(|source| {
+        if source.is_eof() {
+            return Error::IncompleteResponse(source.to_string());
+        }
+        let mut body_snippet = body.chars().take(BODY_SNIPPET_LEN).collect::<String>();
+        if body_snippet.len() < body.len() {
+            body_snippet.push('…');
+        }
+        Error::Decode { source, body_snippet }
+    })
+}
+
+/// The status of a response, regardless of its data
+#[derive(Debug, Deserialize)]
+struct Status {
+    code: OsrmStatus,
+    message: Option<String>,
+}
+/// Deserializes the body of an OSRM response. The failed requests are answered
+/// with a code and a message but without any of the data of the service: when
+/// such a body cannot be decoded, it yields a `ProtocolError` rather than a 
+/// `Decode` error.
+fn decode_response<T: DeserializeOwned>(body: &str) -> Result<Response<T>, Error> {
+    decode::<Response<T>>(body).map_err(|error| {
+        match serde_json::from_str::<Status>(body) {
+            Ok(Status { code, message }) if !matches!(code, OsrmStatus::Ok) => 
+                Error::ProtocolError { status: code, message },
+            _ => error
+        }
+    })
+}
+
+/// Tells apart the json errors due to a body ending prematurely (which yield an
+/// `IncompleteResponse`) from the other ones
+pub(crate) fn json_error(error: serde_json::Error) -> Error {
+    if error.is_eof() {
+        Error::IncompleteResponse(error.to_string())
+    } else {
+        Error::JsonError(error)
+    }
+}
+
+/// Sends a GET request to the given url and returns the raw body of the response.
+/// The metrics of the client are not updated as the response still needs parsing.
+pub(crate) async fn execute_raw(client: &Client, service: Service, coordinates: &Coordinates, url: &str, options: &[(&'static str, String)]) -> Result<bytes::Bytes, Error> {
+    log_request(client, service, coordinates, options);
+    let (_headers, body) = client.fetch(url, options).await?;
+    Ok(body)
+}
+
+/// Checks that the given waypoints indices include the first and last coordinates
+/// and that all of them designate one of the coordinates.
+pub(crate) fn validate_waypoints(waypoints: Option<&Vec<usize>>, coordinates: &Coordinates) -> Result<(), Error> {
+    let waypoints = match waypoints {
+        Some(waypoints) => waypoints,
+        None => return Ok(())
+    };
+    let count = coordinates.count()?;
+    if count == 0 {
+        return Err(Error::InvalidWaypoints("there are no coordinates".to_string()));
+    }
+    if waypoints.first() != Some(&0) || waypoints.last() != Some(&(count - 1)) {
+        return Err(Error::InvalidWaypoints(
+            format!("the first (0) and last ({}) coordinates must be waypoints", count - 1)));
+    }
+    if let Some(index) = waypoints.iter().find(|i| **i >= count) {
+        return Err(Error::InvalidWaypoints(
+            format!("index {index} is out of bounds ({count} coordinates)")));
+    }
+    Ok(())
+}
+
+/// Checks that a per-coordinate option (if set) comprises exactly one value per coordinate
+pub(crate) fn validate_length<T>(option: &'static str, values: Option<&Vec<T>>, coordinates: &Coordinates) -> Result<(), Error> {
+    if let Some(values) = values {
+        let expected = coordinates.count()?;
+        if values.len() != expected {
+            return Err(Error::OptionLengthMismatch { option, expected, actual: values.len() });
+        }
+    }
+    Ok(())
+}
+
+/// Sets the value of the given option, replacing any previous value it might have had
+pub(crate) fn set_option(options: &mut Vec<(&'static str, String)>, name: &'static str, value: impl Display) {
+    options.retain(|(n, _)| *n != name);
+    options.push((name, format!("{value}")));
+}
+
+/// Formats a decimal number for the url of a request. All the numbers sent to
+/// OSRM go through this helper: the formatting of Rust numbers does not depend 
+/// on the locale, and this is where that guarantee is made explicit. The decimal
+/// separator is always a `.` and scientific notation is never used.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Decimal<T>(pub T);
+impl Display for Decimal<f32> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl Display for Decimal<f64> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 pub(crate) fn multi(xs: &[impl Display]) -> String {
     let mut out = String::new();
     for (i, x) in xs.iter().enumerate() {
@@ -199,4 +775,124 @@ pub(crate) fn multi(xs: &[impl Display]) -> String {
         }
     }
     out
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use reqwest::header::{HeaderMap, HeaderValue, HOST, CONTENT_LENGTH, AUTHORIZATION, USER_AGENT};
+    use crate::{Client, ClientMetrics, Coordinates, Location, Decimal, Error, OsrmStatus, RouteResponse, Service};
+    use super::{check_complete, decode, decode_response, request_summary, Response};
+
+    #[test]
+    fn decode_errors_carry_a_body_snippet() {
+        let body = r#"{"code":"Ok","routes":[{"distance":"far"}]}"#;
+        match decode::<Response<RouteResponse>>(body) {
+            Err(Error::Decode { body_snippet, .. }) => assert_eq!(body, body_snippet),
+            _ => panic!("expected a decode error"),
+        }
+
+        let body = format!("[{}]", "1,".repeat(500));
+        match decode::<Response<RouteResponse>>(&body) {
+            Err(Error::Decode { body_snippet, .. }) => {
+                assert_eq!(257, body_snippet.chars().count());
+                assert!(body_snippet.ends_with('…'));
+            },
+            _ => panic!("expected a decode error"),
+        }
+    }
+
+    #[test]
+    fn truncated_bodies_are_incomplete_responses() {
+        let body = r#"{"code":"Ok","routes":[{"distance":12"#;
+        assert!(matches!(decode::<Response<RouteResponse>>(body), Err(Error::IncompleteResponse(_))));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("64"));
+        assert!(matches!(check_complete(&headers, &body.into()), Err(Error::IncompleteResponse(_))));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("32"));
+        assert!(check_complete(&headers, &body.into()).is_ok());
+        assert!(check_complete(&HeaderMap::new(), &body.into()).is_ok());
+    }
+
+    #[test]
+    fn protocol_errors_carry_the_message() {
+        let body = r#"{"code":"NoRoute","message":"Impossible route between points"}"#;
+        match decode_response::<RouteResponse>(body) {
+            Err(Error::ProtocolError { status: OsrmStatus::NoRoute, message }) => 
+                assert_eq!(Some("Impossible route between points".to_string()), message),
+            _ => panic!("expected a protocol error"),
+        }
+    }
+
+    #[test]
+    fn request_summary_redacts_coordinates() {
+        let coordinates = Coordinates::Multi(vec![Location::new(4.35, 50.85), Location::new(4.40, 50.80)]);
+        let options = [("steps", "true".to_string()), ("snapping", "any".to_string())];
+        let summary = request_summary(Service::Route, &coordinates, &options);
+        assert_eq!("osrm route request with 2 coordinates: steps=true&snapping=any", summary);
+        assert!(!summary.contains("4.35"));
+    }
+
+    #[test]
+    fn metrics_are_shared_among_clones() {
+        let client = Client::default();
+        let clone  = client.clone();
+        client.record(&Ok(()), Duration::from_millis(10));
+        clone.record(&Ok(()), Duration::from_millis(20));
+        clone.record::<()>(&Err(Error::ProtocolError { status: OsrmStatus::NoRoute, message: None }), Duration::from_millis(5));
+        client.record::<()>(&Err(Error::MissingData("routes")), Duration::from_millis(1));
+
+        let expected = ClientMetrics { requests: 4, successes: 2, protocol_errors: 1, http_errors: 1, total_latency: Duration::from_millis(36) };
+        assert_eq!(expected, client.metrics());
+        assert_eq!(expected, clone.metrics());
+    }
+
+    #[test]
+    fn decimals_use_a_dot_and_no_exponent() {
+        assert_eq!("13.88",     Decimal(13.88_f32).to_string());
+        assert_eq!("13.88",     Decimal(13.88_f64).to_string());
+        assert_eq!("0.0000001", Decimal(1e-7_f64).to_string());
+        assert_eq!("1000000",   Decimal(1e6_f32).to_string());
+    }
+
+    #[test]
+    fn default_headers_are_merged_without_reserved_ones() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION,  HeaderValue::from_static("secret"));
+        headers.insert(HOST,           HeaderValue::from_static("example.org"));
+        headers.insert(CONTENT_LENGTH, HeaderValue::from_static("42"));
+
+        let client = Client::default()
+            .header(USER_AGENT, HeaderValue::from_static("osrm-client"))
+            .default_headers(headers);
+
+        assert_eq!(2, client.headers.len());
+        assert_eq!("secret",      client.headers[AUTHORIZATION]);
+        assert_eq!("osrm-client", client.headers[USER_AGENT]);
+    }
+
+    #[test]
+    fn long_requests_are_posted() {
+        let url     = "http://localhost:5000/table/v1/car/4.35,50.85;4.4,50.8";
+        let options = [("annotations", "duration".to_string())];
+        assert_eq!(None, Client::default().post_form(url, &options));
+        assert_eq!(None, Client::default().post_threshold(200).post_form(url, &options));
+
+        let (path, form) = Client::default().post_threshold(50).post_form(url, &options).unwrap();
+        assert_eq!("http://localhost:5000/table/v1/car", path);
+        assert_eq!(vec![("coordinates", "4.35,50.85;4.4,50.8".to_string()), ("annotations", "duration".to_string())], form);
+    }
+
+    #[test]
+    fn coordinates_are_encoded_as_polyline_beyond_threshold() {
+        let coords = Coordinates::Multi(vec![Location::new(-120.2, 38.5), Location::new(-120.95, 40.7)]);
+
+        let client = Client::default();
+        assert_eq!("-120.2,38.5;-120.95,40.7", coords.for_url(&client).to_string());
+        let client = Client::default().auto_polyline_threshold(2);
+        assert_eq!("-120.2,38.5;-120.95,40.7", coords.for_url(&client).to_string());
+        let client = Client::default().auto_polyline_threshold(1);
+        assert_eq!("polyline6(_izlhAxrlgdFa{geC~ywl@)", coords.for_url(&client).to_string());
+    }
+}
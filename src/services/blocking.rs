@@ -0,0 +1,143 @@
+//! This module lets the requests be sent synchronously, without any async
+//! runtime (feature `blocking`). This suits the CLI tools and scripts which
+//! only send a handful of requests.
+
+use std::time::Instant;
+
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+use serde::de::DeserializeOwned;
+
+use crate::{Client, Coordinates, Error, Service, check_complete, log_request, parse};
+
+/// The client which sends the requests synchronously (see e.g. `RouteRequest::send_blocking`).
+/// It is configured by a regular `Client`: its base url, version, default
+/// profile and radius, headers, timeout, retry policy,... all apply. The metrics
+/// are shared with that client.
+///
+/// Limitations: the http settings of the `ClientBuilder` (`user_agent`, `proxy`
+/// and `http2_prior_knowledge`) are those of the blocking http client (see
+/// `BlockingClient::with_reqwest_client`) and the requests are never sent over
+/// a unix socket. Beware: like all blocking http clients, this one must not be
+/// used from within an async runtime.
+#[derive(Debug, Clone)]
+pub struct BlockingClient {
+    /// The configuration of the requests
+    pub(crate) client: Client,
+    /// The http client which actually sends the requests
+    reqwest: reqwest::blocking::Client,
+}
+
+impl Default for BlockingClient {
+    fn default() -> Self {
+        Self::new(Client::default())
+    }
+}
+impl From<Client> for BlockingClient {
+    fn from(client: Client) -> Self {
+        Self::new(client)
+    }
+}
+
+impl BlockingClient {
+    /// Creates a blocking client which sends the requests as configured by `client`
+    pub fn new(client: Client) -> Self {
+        Self { client, reqwest: reqwest::blocking::Client::new() }
+    }
+    /// Sends the requests with the given preconfigured blocking http client
+    pub fn with_reqwest_client(mut self, reqwest: reqwest::blocking::Client) -> Self {
+        self.reqwest = reqwest;
+        self
+    }
+    /// The configuration of the requests
+    pub fn config(&self) -> &Client {
+        &self.client
+    }
+    /// Sends a GET request to the given url (along with the default headers and
+    /// the given query options) and returns the headers and body of the response.
+    /// The request is posted instead when its url exceeds the `post_threshold`.
+    pub(crate) fn fetch(&self, url: &str, options: &[(&'static str, String)]) -> Result<(HeaderMap, Bytes), Error> {
+        let mut request = match self.client.post_form(url, options) {
+            Some((url, form)) => self.reqwest.post(url).form(&form),
+            None => self.reqwest.get(url).query(options),
+        }.headers(self.client.headers.clone());
+        if let Some(timeout) = self.client.timeout {
+            request = request.timeout(timeout);
+        }
+        let response = request.send()?;
+        let status   = response.error_for_status_ref().map(|_| ());
+        let headers  = response.headers().clone();
+        let body     = response.bytes()?;
+        self.client.check_status(status, &body)?;
+        check_complete(&headers, &body)?;
+        Ok((headers, body))
+    }
+}
+
+/// Sends a GET request to the given url and interprets the OSRM response,
+/// blocking the current thread (see `execute`).
+pub(crate) fn execute_blocking<T: DeserializeOwned>(client: &BlockingClient, service: Service, coordinates: &Coordinates, url: &str, options: &[(&'static str, String)]) -> Result<(T, HeaderMap), Error> {
+    let config = &client.client;
+    #[cfg(feature = "cache")]
+    if let Some(outcome) = crate::from_cache(config, url, options) {
+        return outcome;
+    }
+    log_request(config, service, coordinates, options);
+    crate::retrying_blocking(config, || {
+        let start   = Instant::now();
+        let outcome = client.fetch(url, options)
+            .and_then(|(headers, body)| parse(config, url, options, headers, body));
+        config.record(&outcome, start.elapsed());
+        outcome
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::{Read, Write}, net::TcpListener, thread};
+
+    use crate::{BlockingClient, Client, Coordinates, Error, Location, OsrmStatus, RouteRequestBuilder, TileRequestBuilder};
+
+    /// Serves the given responses (one per connection) and returns the requests received
+    fn serve(responses: Vec<(&'static str, &'static str)>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address  = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            responses.into_iter().map(|(content_type, body)| {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = vec![0; 4096];
+                let n = stream.read(&mut request).unwrap();
+                let response = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+                stream.write_all(response.as_bytes()).unwrap();
+                String::from_utf8_lossy(&request[..n]).into_owned()
+            })
+            .collect()
+        });
+        (format!("http://{address}"), server)
+    }
+
+    #[test]
+    fn requests_are_sent_synchronously() {
+        let (base_url, server) = serve(vec![
+            ("application/json", r#"{"code":"Ok","waypoints":[],"routes":[{"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}]}"#),
+            ("application/json", r#"{"code":"NoRoute","message":"Impossible route between points"}"#),
+            ("application/x-protobuf", "tile"),
+        ]);
+        let client  = BlockingClient::new(Client::default().base_url(base_url));
+        let request = RouteRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.35, 50.85), Location::new(4.40, 50.80)]))
+            .build()
+            .unwrap();
+
+        assert_eq!(1, request.send_blocking(&client).unwrap().routes.len());
+        assert!(matches!(request.send_blocking(&client),
+            Err(Error::ProtocolError { status: OsrmStatus::NoRoute, message: Some(_) })));
+        let tile = TileRequestBuilder::default().x(2096.0).y(1373.0).zoom(12).build().unwrap();
+        assert_eq!("tile", tile.send_blocking(&client).unwrap());
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("GET /route/v1/car/4.35,50.85;4.4,50.8?"), "{}", requests[0]);
+        assert!(requests[2].starts_with("GET /tile/v1/car/tile(2096,1373,12).mvt"), "{}", requests[2]);
+        assert_eq!(3, client.config().metrics().requests);
+    }
+}
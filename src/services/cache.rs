@@ -0,0 +1,206 @@
+//! This module implements an optional in-memory cache of the responses sent by
+//! OSRM (feature `cache`). It saves the redundant calls of the applications
+//! which repeatedly send the same queries against static data.
+
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use bytes::Bytes;
+use reqwest::header::HeaderMap;
+
+use crate::Client;
+
+/// A least recently used cache of the successful responses, keyed on the url
+/// and options of the requests. It is shared among the clones of a client.
+#[derive(Debug, Clone)]
+pub(crate) struct ResponseCache {
+    /// The maximum number of responses kept in the cache
+    capacity: usize,
+    /// The duration during which a response is served from the cache
+    ttl: Duration,
+    /// The cached responses
+    entries: Arc<Mutex<Entries>>,
+}
+
+/// The content of a response cache
+#[derive(Debug, Default)]
+struct Entries {
+    /// The cached responses, by key
+    responses: HashMap<String, Entry>,
+    /// A logical clock telling which response was used least recently
+    clock: u64,
+    /// The version of the data the cached responses were computed from (if known)
+    data_version: Option<String>,
+}
+
+/// One cached response
+#[derive(Debug)]
+struct Entry {
+    headers: HeaderMap,
+    body: Bytes,
+    /// When the response was received
+    stored: Instant,
+    /// The logical time of the last use of the response
+    used: u64,
+}
+
+impl Client {
+    /// Keeps the successful responses in an in-memory cache holding at most
+    /// `capacity` responses (the least recently used ones are evicted first).
+    /// A request identical to a previous one (same url and options) is then
+    /// answered from the cache, without reaching the server, for `ttl` after
+    /// the response was received. The cache is shared among the clones of the
+    /// client.
+    ///
+    /// Only enable this when the data of the OSRM instance is static: the cache
+    /// is flushed when a response reports a `data_version` that differs from
+    /// the one of the cached responses, but that is only noticed upon a miss (and
+    /// requires OSRM to report the version of its data).
+    pub fn with_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(ResponseCache::new(capacity, ttl));
+        self
+    }
+}
+
+impl ResponseCache {
+    /// Creates an empty cache
+    fn new(capacity: usize, ttl: Duration) -> Self {
+        Self { capacity, ttl, entries: Default::default() }
+    }
+    /// The headers and body of the cached response to the given request (if
+    /// still fresh)
+    pub(crate) fn get(&self, url: &str, options: &[(&'static str, String)]) -> Option<(HeaderMap, Bytes)> {
+        let key = key(url, options);
+        let mut entries = self.entries.lock().expect("poisoned cache");
+        entries.clock += 1;
+        let clock = entries.clock;
+        match entries.responses.get_mut(&key) {
+            Some(entry) if entry.stored.elapsed() < self.ttl => {
+                entry.used = clock;
+                Some((entry.headers.clone(), entry.body.clone()))
+            },
+            Some(_) => {
+                entries.responses.remove(&key);
+                None
+            },
+            None => None
+        }
+    }
+    /// Caches the response to the given request. All the cached responses are
+    /// dropped when the response stems from another version of the data.
+    pub(crate) fn insert(&self, url: &str, options: &[(&'static str, String)], data_version: Option<&str>, headers: HeaderMap, body: Bytes) {
+        if self.capacity == 0 {
+            return;
+        }
+        let key = key(url, options);
+        let mut entries = self.entries.lock().expect("poisoned cache");
+        if let Some(version) = data_version {
+            if entries.data_version.as_deref() != Some(version) {
+                entries.responses.clear();
+                entries.data_version = Some(version.to_string());
+            }
+        }
+        if !entries.responses.contains_key(&key) && entries.responses.len() >= self.capacity {
+            let lru = entries.responses.iter()
+                .min_by_key(|(_, entry)| entry.used)
+                .map(|(key, _)| key.clone());
+            if let Some(lru) = lru {
+                entries.responses.remove(&lru);
+            }
+        }
+        entries.clock += 1;
+        let used = entries.clock;
+        entries.responses.insert(key, Entry { headers, body, stored: Instant::now(), used });
+    }
+}
+
+/// The key identifying a request in the cache
+fn key(url: &str, options: &[(&'static str, String)]) -> String {
+    let options = options.iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+    format!("{url}?{options}")
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use bytes::Bytes;
+    use reqwest::header::HeaderMap;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::ResponseCache;
+    use crate::{Client, Location, NearestRequestBuilder};
+
+    fn body(cache: &ResponseCache, url: &str) -> Option<Bytes> {
+        cache.get(url, &[]).map(|(_, body)| body)
+    }
+
+    #[test]
+    fn least_recently_used_responses_are_evicted() {
+        let cache = ResponseCache::new(2, Duration::from_secs(60));
+        cache.insert("a", &[], None, HeaderMap::new(), Bytes::from("A"));
+        cache.insert("b", &[], None, HeaderMap::new(), Bytes::from("B"));
+        assert_eq!(Some(Bytes::from("A")), body(&cache, "a"));
+        cache.insert("c", &[], None, HeaderMap::new(), Bytes::from("C"));
+
+        assert_eq!(Some(Bytes::from("A")), body(&cache, "a"));
+        assert_eq!(None, body(&cache, "b"));
+        assert_eq!(Some(Bytes::from("C")), body(&cache, "c"));
+    }
+    #[test]
+    fn requests_differing_by_their_options_are_distinct() {
+        let cache = ResponseCache::new(2, Duration::from_secs(60));
+        cache.insert("a", &[("steps", "true".to_string())], None, HeaderMap::new(), Bytes::from("A"));
+        assert!(cache.get("a", &[("steps", "true".to_string())]).is_some());
+        assert!(cache.get("a", &[("steps", "false".to_string())]).is_none());
+    }
+    #[test]
+    fn stale_responses_are_not_served() {
+        let cache = ResponseCache::new(2, Duration::ZERO);
+        cache.insert("a", &[], None, HeaderMap::new(), Bytes::from("A"));
+        assert_eq!(None, body(&cache, "a"));
+    }
+    #[test]
+    fn new_data_version_flushes_the_cache() {
+        let cache = ResponseCache::new(4, Duration::from_secs(60));
+        cache.insert("a", &[], Some("2024-01-01"), HeaderMap::new(), Bytes::from("A"));
+        cache.insert("b", &[], None, HeaderMap::new(), Bytes::from("B"));
+        cache.insert("c", &[], Some("2024-01-01"), HeaderMap::new(), Bytes::from("C"));
+        assert!(body(&cache, "a").is_some());
+
+        cache.insert("d", &[], Some("2024-02-01"), HeaderMap::new(), Bytes::from("D"));
+        assert_eq!(None, body(&cache, "a"));
+        assert_eq!(None, body(&cache, "b"));
+        assert_eq!(Some(Bytes::from("D")), body(&cache, "d"));
+    }
+
+    #[tokio::test]
+    async fn repeated_requests_are_answered_from_the_cache() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        // the server answers one single request
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let body = r#"{"code":"Ok","data_version":"2024-01-01","waypoints":[{"hint":"","distance":1.0,"name":"Rue Haute","location":[4.35,50.85]}]}"#;
+            let response = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+        });
+
+        let client = Client::default()
+            .base_url(format!("http://{address}"))
+            .with_cache(8, Duration::from_secs(60));
+        let request = NearestRequestBuilder::default()
+            .coordinates(Location::new(4.35, 50.85))
+            .build()
+            .unwrap();
+        for _ in 0..3 {
+            let response = request.send(&client).await.unwrap();
+            assert_eq!("Rue Haute", response.nearest().unwrap().name);
+        }
+        assert_eq!(1, client.metrics().requests);
+    }
+}
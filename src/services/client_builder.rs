@@ -0,0 +1,333 @@
+//! This module defines the builder which gathers all the configuration knobs
+//! of a client in one place.
+
+use std::time::Duration;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::{Client, Error, Radius, RetryPolicy, TransportationMode};
+
+/// Configures a `Client` (see `Client::builder`). All settings are optional:
+/// building a default `ClientBuilder` yields the same client as `Client::default()`.
+#[derive(Debug, Clone, Default)]
+pub struct ClientBuilder {
+    /// The base url of the OSRM instance
+    base_url: Option<String>,
+    /// The path under which the services are mounted
+    service_prefix: Option<String>,
+    /// The version of the API
+    version: Option<String>,
+    /// The profile used by the requests that do not specify theirs
+    default_profile: Option<TransportationMode>,
+    /// Whether `NoSegment` failures are retried with `snapping=any`
+    snapping_fallback: bool,
+    /// The number of coordinates beyond which these are encoded as a polyline
+    auto_polyline_threshold: Option<usize>,
+    /// The length of url beyond which the requests are sent with POST
+    post_threshold: Option<usize>,
+    /// The headers sent along with every request
+    headers: HeaderMap,
+    /// The maximum duration of a request (from connection to end of the body)
+    timeout: Option<Duration>,
+    /// How the requests failing with a transient error are sent again
+    retry_policy: RetryPolicy,
+    /// The value of the `User-Agent` header
+    user_agent: Option<String>,
+    /// The url of the proxy all requests go through
+    proxy: Option<String>,
+    /// Whether the options of each request are logged
+    log_requests: bool,
+    /// The radius applied to the requests which leave their radiuses unset
+    default_radius: Option<Radius>,
+    /// Whether HTTP/2 is used right away, without negotiation
+    http2_prior_knowledge: bool,
+    /// The preconfigured http client to use instead of building one
+    reqwest: Option<reqwest::Client>,
+}
+
+/// The name of the header conveying the api key read by `Client::from_env`
+const API_KEY_HEADER: &str = "x-api-key";
+
+impl Client {
+    /// Starts the configuration of a new client
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+    /// Configures a client from the following environment variables, all of
+    /// which are optional (the defaults are used for the missing ones):
+    /// 
+    /// * `OSRM_BASE_URL`: the base url of the OSRM instance
+    /// * `OSRM_VERSION`: the version of the API
+    /// * `OSRM_TIMEOUT_SECS`: the timeout of the requests, in whole seconds
+    /// * `OSRM_API_KEY`: an api key sent along with every request in the `X-Api-Key` header
+    /// 
+    /// This fails with a `ParseError` when one of the variables holds a malformed value.
+    pub fn from_env() -> Result<Client, Error> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+    /// Configures a client from the variables looked up with the given function
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Client, Error> {
+        let mut builder = Client::builder();
+        if let Some(base_url) = var("OSRM_BASE_URL") {
+            builder = builder.base_url(base_url);
+        }
+        if let Some(version) = var("OSRM_VERSION") {
+            builder = builder.version(version);
+        }
+        if let Some(timeout) = var("OSRM_TIMEOUT_SECS") {
+            let secs = timeout.trim().parse::<u64>()
+                .map_err(|_| Error::ParseError(format!("OSRM_TIMEOUT_SECS '{timeout}' (expected a number of seconds)")))?;
+            builder = builder.timeout(Duration::from_secs(secs));
+        }
+        if let Some(api_key) = var("OSRM_API_KEY") {
+            let value = HeaderValue::from_str(&api_key)
+                .map_err(|_| Error::ParseError("OSRM_API_KEY (not a valid header value)".to_string()))?;
+            builder = builder.header(HeaderName::from_static(API_KEY_HEADER), value);
+        }
+        builder.build()
+    }
+}
+
+impl ClientBuilder {
+    /// Sets the base url of the OSRM instance (see `Client::base_url`)
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = Some(url.into());
+        self
+    }
+    /// Sets the path under which the services are mounted (see `Client::service_prefix`)
+    pub fn service_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.service_prefix = Some(prefix.into());
+        self
+    }
+    /// Sets the protocol version (see `Client::version`)
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+    /// Sets the profile of the requests which leave theirs unset (see `Client::default_profile`)
+    pub fn default_profile(mut self, profile: TransportationMode) -> Self {
+        self.default_profile = Some(profile);
+        self
+    }
+    /// Retries `NoSegment` failures with `snapping=any` (see `Client::snapping_fallback`)
+    pub fn snapping_fallback(mut self, enabled: bool) -> Self {
+        self.snapping_fallback = enabled;
+        self
+    }
+    /// Encodes long sequences of coordinates as polylines (see `Client::auto_polyline_threshold`)
+    pub fn auto_polyline_threshold(mut self, n: usize) -> Self {
+        self.auto_polyline_threshold = Some(n);
+        self
+    }
+    /// Sends the requests with too long an url with POST (see `Client::post_threshold`)
+    pub fn post_threshold(mut self, n: usize) -> Self {
+        self.post_threshold = Some(n);
+        self
+    }
+    /// Adds the given headers to the ones sent along with every request (see `Client::default_headers`)
+    pub fn default_headers(mut self, headers: HeaderMap) -> Self {
+        self.headers.extend(headers);
+        self
+    }
+    /// Adds one header to the ones sent along with every request (see `Client::header`)
+    pub fn header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+    /// Sets the maximum duration of a request, from the connection until the
+    /// end of the response body (see `Client::with_timeout`).
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+    /// Sets the number of times a request is sent again when it fails with a
+    /// transient error (see `RetryPolicy`), keeping the other settings of the 
+    /// retry policy. No request is retried by default.
+    pub fn retries(mut self, retries: usize) -> Self {
+        self.retry_policy.set_max_attempts(retries.saturating_add(1));
+        self
+    }
+    /// Sets how the requests failing with a transient error are sent again (see
+    /// `Client::retry_policy`)
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+    /// Sets the `User-Agent` header sent along with every request. The usage
+    /// policy of the public demo server asks for a user agent identifying your app.
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+    /// Routes all the requests (http and https) through the proxy at the given url
+    pub fn proxy(mut self, url: impl Into<String>) -> Self {
+        self.proxy = Some(url.into());
+        self
+    }
+    /// Logs the options of each request (see `Client::log_requests`)
+    pub fn log_requests(mut self, enabled: bool) -> Self {
+        self.log_requests = enabled;
+        self
+    }
+    /// Sets the radius of the requests which leave their radiuses unset (see `Client::default_radius`)
+    pub fn default_radius(mut self, radius: Radius) -> Self {
+        self.default_radius = Some(radius);
+        self
+    }
+    /// Speaks HTTP/2 right away, without negotiating the protocol first. Only 
+    /// enable this when the OSRM instance (or the gateway in front of it) is 
+    /// known to accept HTTP/2: typically an h2c (cleartext HTTP/2) endpoint, as 
+    /// HTTP/2 over TLS is negotiated automatically anyway (through ALPN). This 
+    /// lets many concurrent requests (e.g. large table workloads) be multiplexed
+    /// over one single connection. Requests to HTTP/1 only servers will fail.
+    pub fn http2_prior_knowledge(mut self, enabled: bool) -> Self {
+        self.http2_prior_knowledge = enabled;
+        self
+    }
+    /// Sends the requests with the given preconfigured http client (e.g. to share
+    /// its connection pool with the rest of your application, or to configure it
+    /// beyond what this builder offers). The `user_agent`, `proxy` and 
+    /// `http2_prior_knowledge` settings of this builder are then ignored: these
+    /// must be set on the given client instead.
+    pub fn reqwest_client(mut self, client: reqwest::Client) -> Self {
+        self.reqwest = Some(client);
+        self
+    }
+    /// Builds the configured client. This fails with an `HttpError` when the
+    /// underlying http client cannot be built (e.g. because the proxy url is invalid).
+    pub fn build(self) -> Result<Client, Error> {
+        let reqwest = match self.reqwest {
+            Some(reqwest) => reqwest,
+            None => {
+                let mut reqwest = reqwest::Client::builder();
+                if let Some(user_agent) = self.user_agent {
+                    reqwest = reqwest.user_agent(user_agent);
+                }
+                if let Some(proxy) = self.proxy {
+                    reqwest = reqwest.proxy(reqwest::Proxy::all(proxy)?);
+                }
+                if self.http2_prior_knowledge {
+                    reqwest = reqwest.http2_prior_knowledge();
+                }
+                reqwest.build()?
+            }
+        };
+
+        let mut client = Client::default()
+            .snapping_fallback(self.snapping_fallback)
+            .log_requests(self.log_requests)
+            .default_headers(self.headers);
+        client.reqwest = reqwest;
+        client.retry_policy = self.retry_policy;
+        client.auto_polyline_threshold = self.auto_polyline_threshold;
+        client.post_threshold = self.post_threshold;
+        client.default_radius = self.default_radius;
+        client.timeout = self.timeout;
+        if let Some(base_url) = self.base_url {
+            client = client.base_url(base_url);
+        }
+        if let Some(prefix) = self.service_prefix {
+            client = client.service_prefix(&prefix);
+        }
+        if let Some(version) = self.version {
+            client = client.version(version);
+        }
+        if let Some(profile) = self.default_profile {
+            client = client.default_profile(profile);
+        }
+        Ok(client)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use reqwest::header::{HeaderValue, HOST};
+
+    use crate::{Client, Error, Radius, TransportationMode};
+
+    #[test]
+    fn default_builder_yields_default_client() {
+        let built   = Client::builder().build().unwrap();
+        let default = Client::default();
+        assert_eq!(default.base_url, built.base_url);
+        assert_eq!(default.version, built.version);
+        assert_eq!(default.retry_policy, built.retry_policy);
+        assert!(built.headers.is_empty());
+    }
+    #[test]
+    fn builder_applies_all_settings() {
+        let client = Client::builder()
+            .base_url("http://localhost:5000")
+            .service_prefix("osrm")
+            .version("v2")
+            .default_profile(TransportationMode::Foot)
+            .snapping_fallback(true)
+            .auto_polyline_threshold(10)
+            .post_threshold(2000)
+            .header("x-api-key".parse().unwrap(), HeaderValue::from_static("secret"))
+            .header(HOST, HeaderValue::from_static("example.com"))
+            .timeout(Duration::from_secs(5))
+            .retries(2)
+            .user_agent("my-app/1.0")
+            .proxy("http://proxy.local:3128")
+            .log_requests(true)
+            .default_radius(Radius::Limited(25.0))
+            .http2_prior_knowledge(true)
+            .build()
+            .unwrap();
+        assert_eq!("http://localhost:5000", client.base_url);
+        assert_eq!("/osrm", client.service_prefix);
+        assert_eq!("v2", client.version);
+        assert_eq!("foot", client.default_profile.as_str());
+        assert!(client.snapping_fallback);
+        assert_eq!(Some(10), client.auto_polyline_threshold);
+        assert_eq!(Some(2000), client.post_threshold);
+        assert_eq!(3, client.retry_policy.max_attempts());
+        assert_eq!(Some(Duration::from_secs(5)), client.timeout);
+        assert!(client.log_requests);
+        assert_eq!("25", client.default_radius.unwrap().to_string());
+        assert_eq!(Some(&HeaderValue::from_static("secret")), client.headers.get("x-api-key"));
+        assert!(client.headers.get(HOST).is_none());
+    }
+    fn vars<'a>(vars: &'a [(&'a str, &'a str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        move |name| vars.iter().find(|(n, _)| *n == name).map(|(_, v)| v.to_string())
+    }
+    #[test]
+    fn from_env_reads_the_osrm_variables() {
+        let client = Client::from_vars(vars(&[
+            ("OSRM_BASE_URL",     "http://osrm.local:5000"),
+            ("OSRM_VERSION",      "v1"),
+            ("OSRM_TIMEOUT_SECS", "30"),
+            ("OSRM_API_KEY",      "secret"),
+        ])).unwrap();
+        assert_eq!("http://osrm.local:5000", client.base_url);
+        assert_eq!(Some(Duration::from_secs(30)), client.timeout);
+        assert_eq!(Some(&HeaderValue::from_static("secret")), client.headers.get("x-api-key"));
+    }
+    #[test]
+    fn from_env_falls_back_to_defaults() {
+        let client = Client::from_vars(vars(&[])).unwrap();
+        assert_eq!(Client::default().base_url, client.base_url);
+        assert!(client.headers.is_empty());
+    }
+    #[test]
+    fn from_env_rejects_malformed_values() {
+        assert!(matches!(Client::from_vars(vars(&[("OSRM_TIMEOUT_SECS", "soon")])), Err(Error::ParseError(_))));
+        assert!(matches!(Client::from_vars(vars(&[("OSRM_API_KEY", "new\nline")])), Err(Error::ParseError(_))));
+    }
+    #[test]
+    fn invalid_proxy_fails_to_build() {
+        assert!(Client::builder().proxy("not a url").build().is_err());
+    }
+    #[test]
+    fn preconfigured_reqwest_client_is_used_as_is() {
+        let reqwest = reqwest::Client::builder().build().unwrap();
+        let client  = Client::builder()
+            .reqwest_client(reqwest)
+            .proxy("not a url")
+            .base_url("http://localhost:5000/")
+            .build()
+            .unwrap();
+        assert_eq!("http://localhost:5000", client.base_url);
+    }
+}
@@ -6,12 +6,14 @@
 //! The algorithm might not be able to match all points. Outliers are removed if they can not be 
 //! matched successfully.
 
+use std::{borrow::Cow, str::FromStr};
+
 use displaythis::Display;
 use serde::{Serialize, Deserialize};
 
 use crate::*;
 
-request!(MatchRequest (Service::Match) -> MatchResponse {
+request!(#[validate] MatchRequest (Service::Match) -> MatchResponse {
     /// Returned route steps for each route
     #[builder(default)]
     steps: bool,
@@ -36,9 +38,30 @@ request!(MatchRequest (Service::Match) -> MatchResponse {
     /// Treats input coordinates indicated by given indices as waypoints in returned Match object. 
     /// Default is to treat all input coordinates as waypoints.
     #[builder(default, setter(into, strip_option))]
-    waypoints: Option<Vec<usize>>
+    waypoints: Option<Vec<usize>>,
+    /// The index in the original trace of each coordinate kept by `simplify_input`
+    #[builder(default, setter(custom))]
+    input_indices: Option<Vec<usize>>,
+    /// The start and interval (in seconds) of the timestamps set by `timestamps_from`
+    #[builder(default, setter(custom))]
+    timestamp_series: Option<(u64, u64)>
 });
 
+impl MatchRequest {
+    /// The timestamps of the input locations, either given explicitly or 
+    /// generated from the series set by `timestamps_from`
+    fn all_timestamps(&self) -> Option<Cow<'_, [u64]>> {
+        match (&self.timestamps, self.timestamp_series) {
+            (Some(timestamps), _) => Some(Cow::Borrowed(timestamps)),
+            (None, Some((start, interval))) => {
+                let count = self.coordinates.count().ok()?;
+                timestamp_series(start, interval, count).map(Cow::Owned)
+            },
+            (None, None) => None,
+        }
+    }
+}
+
 impl WithOptions for MatchRequest {
     fn options(&self) -> Vec<(&'static str, String)> {
         let mut opts = vec![];
@@ -46,13 +69,137 @@ impl WithOptions for MatchRequest {
         add_option!(opt       opts, annotations,       self.annotations);
         add_option!(opt       opts, geometries,        self.geometries);
         add_option!(opt       opts, overview,          self.overview);
-        add_option!(opt multi opts, timestamps,        self.timestamps);
+        add_option!(opt multi opts, timestamps,        self.all_timestamps());
         add_option!(opt multi opts, radiuses,          self.radiuses);
         add_option!(opt       opts, gaps,              self.gaps);
         add_option!(          opts, tidy,              self.tidy);
         add_option!(opt multi opts, waypoints,         self.waypoints);
         opts
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        validate_waypoints(self.waypoints.as_ref(), &self.coordinates)?;
+        if let Some(timestamps) = self.all_timestamps() {
+            let count = self.coordinates.count()?;
+            if timestamps.len() != count {
+                return Err(Error::InvalidTimestamps(
+                    format!("{} timestamps for {count} coordinates", timestamps.len())));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MatchRequestBuilder {
+    /// Sets one timestamp per coordinate, starting at `start` (in seconds since
+    /// UNIX epoch) and spaced by `interval_secs`. This suits GPS logs sampled at
+    /// a fixed rate and yields monotonically increasing timestamps by construction.
+    /// 
+    /// The timestamps are generated for the coordinates of the request, which
+    /// may be set before or after. `build` fails with a validation error when 
+    /// the number of coordinates is unknown, when explicit `timestamps` are set
+    /// as well, or when the last timestamp would overflow.
+    pub fn timestamps_from(&mut self, start: u64, interval_secs: u64) -> &mut Self {
+        self.timestamp_series = Some(Some((start, interval_secs)));
+        self
+    }
+    /// Checks that the timestamps set by `timestamps_from` can be generated
+    fn validate(&self) -> Result<(), String> {
+        let Some(Some((start, interval))) = self.timestamp_series else {
+            return Ok(());
+        };
+        if let Some(Some(_)) = self.timestamps {
+            return Err("timestamps_from cannot be combined with timestamps".to_string());
+        }
+        let count = match &self.coordinates {
+            Some(coordinates) => coordinates.count().map_err(|e| e.to_string())?,
+            None => return Err("timestamps_from requires the coordinates to be set".to_string()),
+        };
+        match timestamp_series(start, interval, count) {
+            Some(_) => Ok(()),
+            None => Err(format!("{count} timestamps from {start} every {interval} seconds overflow")),
+        }
+    }
+    /// Thins the input trace client-side before it is sent: the Douglas–Peucker
+    /// algorithm drops the coordinates which are within `tolerance_meters` of 
+    /// the simplified trace. This speeds up the matching of noisy high-frequency
+    /// traces. Unlike the `tidy` option (applied by OSRM), the dropped points
+    /// are never sent. The timestamps, radiuses, bearings, hints and approaches 
+    /// of the dropped points are dropped along with them; the coordinates 
+    /// listed as `waypoints` are always kept.
+    /// 
+    /// Beware: the indices in the response (e.g. of the tracepoints) and in the
+    /// `waypoints` option then refer to the kept coordinates. The waypoints set
+    /// beforehand are remapped; use `MatchResponse::original_indices` to relate
+    /// the tracepoints with the original trace. The coordinates, and all the per
+    /// coordinate options, must be set beforehand. Only explicit sequences of 
+    /// coordinates are simplified (polylines are left untouched).
+    pub fn simplify_input(&mut self, tolerance_meters: f32) -> &mut Self {
+        let locations = match self.coordinates.as_ref() {
+            Some(Coordinates::Multi(locations)) => locations.clone(),
+            _ => return self,
+        };
+        let mut keep = simplify(&locations, tolerance_meters);
+        if let Some(Some(waypoints)) = self.waypoints.as_ref() {
+            for waypoint in waypoints.iter() {
+                if let Some(keep) = keep.get_mut(*waypoint) {
+                    *keep = true;
+                }
+            }
+        }
+        let kept = (0..keep.len()).filter(|i| keep[*i]).collect::<Vec<_>>();
+
+        // the generated timestamps must be those of the original trace
+        if let (Some(Some((start, interval))), None | Some(None)) = (self.timestamp_series, &self.timestamps) {
+            if let Some(timestamps) = timestamp_series(start, interval, locations.len()) {
+                self.timestamps = Some(Some(timestamps));
+                self.timestamp_series = None;
+            }
+        }
+
+        retain_kept(&mut self.timestamps, &keep);
+        retain_kept(&mut self.radiuses, &keep);
+        retain_kept(&mut self.bearings, &keep);
+        retain_kept(&mut self.hints, &keep);
+        retain_kept(&mut self.approaches, &keep);
+        if let Some(Some(waypoints)) = self.waypoints.as_mut() {
+            for waypoint in waypoints.iter_mut() {
+                *waypoint = kept.partition_point(|i| i < waypoint);
+            }
+        }
+        self.coordinates = Some(Coordinates::Multi(kept.iter().map(|i| locations[*i]).collect()));
+        self.input_indices = Some(Some(kept));
+        self
+    }
+}
+
+/// The `count` timestamps starting at `start` and spaced by `interval` seconds
+/// (None when they overflow)
+fn timestamp_series(start: u64, interval: u64, count: usize) -> Option<Vec<u64>> {
+    (0..count as u64)
+        .map(|i| i.checked_mul(interval).and_then(|offset| offset.checked_add(start)))
+        .collect()
+}
+
+/// Tells which of the locations are kept by the Douglas–Peucker simplification
+/// with the given tolerance (in meters). The locations are projected with web
+/// mercator whose scale factor at the latitude of the first location is used
+/// to convert the tolerance.
+fn simplify(locations: &[Location], tolerance_meters: f32) -> Vec<bool> {
+    let scale = locations.first()
+        .map_or(1.0, |first| f64::from(first.latitude).to_radians().cos().recip());
+    let points = locations.iter().map(Location::to_web_mercator).collect::<Vec<_>>();
+    douglas_peucker(&points, f64::from(tolerance_meters) * scale)
+}
+/// Drops the values (one per coordinate) of the coordinates that are not kept.
+/// The values are left untouched when their count does not match the coordinates.
+fn retain_kept<T>(values: &mut Option<Option<Vec<T>>>, keep: &[bool]) {
+    if let Some(Some(values)) = values.as_mut() {
+        if values.len() == keep.len() {
+            let mut kept = keep.iter();
+            values.retain(|_| *kept.next().unwrap_or(&true));
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -60,10 +207,63 @@ pub struct MatchResponse {
     /// Array of Waypoint objects representing all points of the trace in order. 
     /// If the trace point was ommited by map matching because it is an outlier, 
     /// the entry will be null.
-    pub tracepoints: Vec<MatchingWaypoint>,
+    pub tracepoints: Vec<Option<MatchingWaypoint>>,
     /// An array of Route objects that assemble the trace. 
     pub matchings: Vec<MatchingRoute>,
 }
+impl MatchResponse {
+    /// Groups each matching (sub-trace) with the trace points that were matched
+    /// to it, in order. The trace points omitted as outliers are left out.
+    pub fn sub_traces(&self) -> Vec<(&MatchingRoute, Vec<&MatchingWaypoint>)> {
+        let mut sub_traces = self.matchings.iter()
+            .map(|matching| (matching, vec![]))
+            .collect::<Vec<_>>();
+        for tracepoint in self.tracepoints.iter().flatten() {
+            if let Some((_, points)) = sub_traces.get_mut(tracepoint.matchings_index) {
+                points.push(tracepoint);
+            }
+        }
+        sub_traces
+    }
+    /// The matchings whose confidence is at least `min` (in order). This is 
+    /// typically used to discard the matchings that are likely to be wrong.
+    pub fn confident_matchings(&self, min: f32) -> Vec<&MatchingRoute> {
+        self.matchings.iter().filter(|matching| matching.confidence >= min).collect()
+    }
+    /// The mean confidence of the matchings (None when there is no matching)
+    pub fn mean_confidence(&self) -> Option<f32> {
+        if self.matchings.is_empty() {
+            None
+        } else {
+            Some(self.matchings.iter().map(|m| m.confidence).sum::<f32>() / self.matchings.len() as f32)
+        }
+    }
+    /// The number of sub-traces the input trace was split into (one per matching).
+    /// A value greater than one means the trace was split, e.g. because of a
+    /// large gap between the timestamps of two points.
+    pub fn split_count(&self) -> usize {
+        self.matchings.len()
+    }
+    /// The index in the original trace of the coordinate each tracepoint stems
+    /// from. These only differ from the index of the tracepoint when the input of
+    /// the `request` was simplified (see `MatchRequestBuilder::simplify_input`).
+    pub fn original_indices(&self, request: &MatchRequest) -> Vec<usize> {
+        (0..self.tracepoints.len())
+            .map(|i| request.input_indices.as_ref().and_then(|kept| kept.get(i).copied()).unwrap_or(i))
+            .collect()
+    }
+    /// The distance (in meters) between each point of the `input` trace and the
+    /// location it was matched to. This quantifies the quality of the matching
+    /// point by point. The points dropped as outliers have no matched location:
+    /// they are skipped, hence the deviations are listed in input order but 
+    /// there may be fewer of them than input points.
+    pub fn deviations(&self, input: &[Location]) -> Vec<f32> {
+        input.iter()
+            .zip(self.tracepoints.iter())
+            .filter_map(|(point, matched)| matched.as_ref().map(|m| point.haversine_distance(&m.waypoint.location)))
+            .collect()
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MatchingWaypoint {
@@ -84,19 +284,218 @@ pub struct MatchingWaypoint {
 pub struct MatchingRoute {
     #[serde(flatten)]
     /// The actual route data
-    route: Route,
+    pub route: Route,
     /// Confidence of the matching. float value between 0 and 1. 1 is very confident that the matching is correct.
-    confidence: f32,
+    pub confidence: f32,
 }
 
 /// Allows the input track splitting based on huge timestamp gaps between points.
-#[derive(Debug, Clone, Copy, Display)]
+#[derive(Debug, Clone, Copy, Display, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
 pub enum GapHandling {
+    #[default]
     #[display("split")]
     Split,
     #[display("ignore")]
     Ignore,
 }
-impl Default for GapHandling {
-    fn default() -> Self { Self::Split }
-}
\ No newline at end of file
+impl FromStr for GapHandling {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "split"  => Ok(Self::Split),
+            "ignore" => Ok(Self::Ignore),
+            _ => Err(Error::ParseError(format!("gaps '{s}' (expected split or ignore)")))
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test {
+    use crate::{MatchResponse, MatchRequestBuilder, MatchRequestBuilderError, Coordinates, Location, WithOptions, Error, GapHandling, Radius, Meters};
+
+    #[test]
+    fn timestamps_from_start_and_interval() {
+        let request = MatchRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![
+                Location::new(4.51, 50.85), 
+                Location::new(4.52, 50.85), 
+                Location::new(4.53, 50.85)
+            ]))
+            .timestamps_from(1_700_000_000, 5)
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+        assert!(request.options().contains(&("timestamps", "1700000000;1700000005;1700000010".to_string())));
+    }
+    #[test]
+    fn timestamps_from_requires_known_coordinates() {
+        let result = MatchRequestBuilder::default()
+            .timestamps_from(1_700_000_000, 5)
+            .build();
+        assert!(matches!(result, Err(MatchRequestBuilderError::ValidationError(_))));
+
+        let result = MatchRequestBuilder::default()
+            .coordinates(Coordinates::Polyline("_p~iF~".to_string()))
+            .timestamps_from(1_700_000_000, 5)
+            .build();
+        assert!(matches!(result, Err(MatchRequestBuilderError::ValidationError(_))));
+    }
+    #[test]
+    fn timestamps_from_may_precede_the_coordinates() {
+        let request = MatchRequestBuilder::default()
+            .timestamps_from(1_700_000_000, 5)
+            .coordinates(Coordinates::Multi(vec![Location::new(4.51, 50.85), Location::new(4.52, 50.85)]))
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+        assert!(request.options().contains(&("timestamps", "1700000000;1700000005".to_string())));
+    }
+    #[test]
+    fn timestamps_from_must_not_overflow() {
+        let coordinates = Coordinates::Multi(vec![Location::new(4.51, 50.85); 3]);
+        let result = MatchRequestBuilder::default()
+            .coordinates(coordinates.clone())
+            .timestamps_from(u64::MAX - 5, 5)
+            .build();
+        assert!(matches!(result, Err(MatchRequestBuilderError::ValidationError(_))));
+        let result = MatchRequestBuilder::default()
+            .coordinates(coordinates.clone())
+            .timestamps_from(0, u64::MAX)
+            .build();
+        assert!(matches!(result, Err(MatchRequestBuilderError::ValidationError(_))));
+
+        let result = MatchRequestBuilder::default()
+            .coordinates(coordinates)
+            .timestamps_from(0, 5)
+            .timestamps(vec![0, 1, 2])
+            .build();
+        assert!(matches!(result, Err(MatchRequestBuilderError::ValidationError(_))));
+    }
+
+    #[test]
+    fn simplify_input_keeps_options_aligned() {
+        // a straight line of 11 points with a 1 meter wiggle, turning at the 6th point
+        let mut locations = (0..6).map(|i| Location::new(4.35 + 0.001 * i as f32, 50.85)).collect::<Vec<_>>();
+        locations[2].latitude += 0.00001;
+        locations.extend((1..6).map(|i| Location::new(4.355, 50.85 + 0.001 * i as f32)));
+        let request = MatchRequestBuilder::default()
+            .coordinates(Coordinates::Multi(locations.clone()))
+            .timestamps((0..11).collect::<Vec<u64>>())
+            .radiuses((0..11).map(|i| Radius::Limited(f64::from(i))).collect::<Vec<_>>())
+            .waypoints(vec![0, 3, 10])
+            .simplify_input(5.0)
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+
+        let options = request.options();
+        assert!(options.contains(&("timestamps", "0;3;5;10".to_string())));
+        assert!(options.contains(&("radiuses", "0;3;5;10".to_string())));
+        assert!(options.contains(&("waypoints", "0;1;3".to_string())));
+        match &request.coordinates {
+            Coordinates::Multi(kept) => assert_eq!(&vec![locations[0], locations[3], locations[5], locations[10]], kept),
+            _ => panic!("expected explicit coordinates"),
+        }
+
+        let mut response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        response.tracepoints.truncate(4);
+        assert_eq!(vec![0, 3, 5, 10], response.original_indices(&request));
+
+        let request = MatchRequestBuilder::default()
+            .coordinates(Coordinates::Multi(locations))
+            .timestamps_from(100, 10)
+            .waypoints(vec![0, 3, 10])
+            .simplify_input(5.0)
+            .build()
+            .unwrap();
+        assert!(request.options().contains(&("timestamps", "100;130;150;200".to_string())));
+    }
+    #[test]
+    fn without_simplification_indices_are_unchanged() {
+        let request = MatchRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.51, 50.85), Location::new(4.52, 50.85)]))
+            .build()
+            .unwrap();
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        assert_eq!(vec![0, 1, 2, 3, 4], response.original_indices(&request));
+    }
+
+    const RESPONSE: &str = r#"
+    {"code":"Ok",
+     "matchings":[
+        {"confidence":0.9,"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":10.0,"duration":10.0,"distance":100.0},
+        {"confidence":0.4,"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":20.0,"duration":20.0,"distance":200.0}
+     ],
+     "tracepoints":[
+        {"matchings_index":0,"waypoint_index":0,"alternatives_count":0,"hint":"a","distance":1.0,"name":"A","location":[4.51,50.85]},
+        {"matchings_index":0,"waypoint_index":1,"alternatives_count":0,"hint":"b","distance":1.0,"name":"B","location":[4.52,50.85]},
+        null,
+        {"matchings_index":1,"waypoint_index":0,"alternatives_count":1,"hint":"c","distance":1.0,"name":"C","location":[4.61,50.66]},
+        {"matchings_index":1,"waypoint_index":1,"alternatives_count":0,"hint":"d","distance":1.0,"name":"D","location":[4.62,50.66]}
+     ]
+    }"#;
+
+    #[test]
+    fn deviations_skip_the_outliers() {
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        let input = vec![
+            Location::new(4.51, 50.85),
+            Location::new(4.52, 50.851),
+            Location::new(4.70, 50.70),
+            Location::new(4.61, 50.66),
+            Location::new(4.621, 50.66),
+        ];
+        let deviations = response.deviations(&input);
+        assert_eq!(4, deviations.len());
+        assert_eq!(0.0, deviations[0]);
+        assert!((deviations[1] - 111.2).abs() < 1.0, "{}", deviations[1]);
+        assert_eq!(0.0, deviations[2]);
+        assert!((deviations[3] - 70.5).abs() < 1.0, "{}", deviations[3]);
+    }
+
+    #[test]
+    fn confident_matchings_filter_by_confidence() {
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        let confident = response.confident_matchings(0.5);
+        assert_eq!(1, confident.len());
+        assert_eq!(Meters(100.0), confident[0].route.distance);
+        assert_eq!(2, response.confident_matchings(0.4).len());
+        assert!(response.confident_matchings(0.95).is_empty());
+    }
+    #[test]
+    fn mean_confidence_of_the_matchings() {
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        assert!((response.mean_confidence().unwrap() - 0.65).abs() < 1e-6);
+
+        let empty = serde_json::from_str::<MatchResponse>(r#"{"tracepoints":[],"matchings":[]}"#).unwrap();
+        assert_eq!(None, empty.mean_confidence());
+    }
+    #[test]
+    fn split_count_is_the_number_of_matchings() {
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        assert_eq!(2, response.split_count());
+    }
+    #[test]
+    fn gap_handling_parses_and_serializes() {
+        for gaps in [GapHandling::Split, GapHandling::Ignore] {
+            assert_eq!(gaps, gaps.to_string().parse().unwrap());
+            assert_eq!(format!("\"{gaps}\""), serde_json::to_string(&gaps).unwrap());
+            assert_eq!(gaps, serde_json::from_str(&format!("\"{gaps}\"")).unwrap());
+        }
+        assert!(matches!("skip".parse::<GapHandling>(), Err(Error::ParseError(_))));
+    }
+    #[test]
+    fn sub_traces_group_tracepoints_by_matching() {
+        let response = serde_json::from_str::<MatchResponse>(RESPONSE).unwrap();
+        let sub_traces = response.sub_traces();
+
+        assert_eq!(2, sub_traces.len());
+        let names = |i: usize| sub_traces[i].1.iter().map(|w| w.waypoint.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["A", "B"], names(0));
+        assert_eq!(vec!["C", "D"], names(1));
+        assert!(std::ptr::eq(sub_traces[1].0, &response.matchings[1]));
+    }
+}
@@ -2,6 +2,8 @@
 //! and interpret it on the way back.
 
 mod base;
+mod client_builder;
+mod retry;
 
 mod nearest_service;
 mod route_service;
@@ -9,11 +11,30 @@ mod table_service;
 mod match_service;
 mod trip_service;
 mod tile_service;
+#[cfg(all(unix, feature = "unix-socket"))]
+mod unix_socket;
+#[cfg(feature = "cache")]
+mod cache;
+#[cfg(feature = "blocking")]
+mod blocking;
 
 pub use base::*;
+pub use client_builder::*;
+pub use retry::{RetryPolicy, DEFAULT_RETRIABLE_STATUSES};
+pub(crate) use retry::retrying;
+#[cfg(feature = "blocking")]
+pub(crate) use retry::retrying_blocking;
 pub use nearest_service::*;
 pub use route_service::*;
 pub use table_service::*;
 pub use match_service::*;
 pub use trip_service::*;
-pub use tile_service::*;
\ No newline at end of file
+pub use tile_service::*;
+#[cfg(all(unix, feature = "unix-socket"))]
+pub(crate) use unix_socket::UnixSocket;
+#[cfg(feature = "cache")]
+pub(crate) use cache::ResponseCache;
+#[cfg(feature = "blocking")]
+pub use blocking::BlockingClient;
+#[cfg(feature = "blocking")]
+pub(crate) use blocking::execute_blocking;
\ No newline at end of file
@@ -2,12 +2,15 @@
 //!
 //! Snaps a coordinate to the street network and returns the nearest n matches.
 //! Where coordinates only supports a single {longitude},{latitude} entry
+//! (which is why the request holds a `SingleCoordinate` rather than `Coordinates`)
 
+use futures_util::{StreamExt, stream};
 use serde::{Serialize, Deserialize};
 
-use crate::{request, Service, WithOptions, Waypoint, add_option};
+use crate::{request, Service, WithOptions, Waypoint, SingleCoordinate, add_option};
+use crate::{Client, Error, Location, TransportationMode, MAX_CONCURRENT_REQUESTS};
 
-request!(NearestRequest (Service::Nearest) -> NearestResponse {
+request!(NearestRequest (Service::Nearest, SingleCoordinate) -> NearestResponse {
     /// Number of nearest segments that should be returned
     #[builder(default, setter(into, strip_option))]
     number: Option<usize>
@@ -31,4 +34,121 @@ pub struct NearestResponse {
     /// array of Waypoint objects sorted by distance to the input coordinate. 
     /// Each object has at least the following additional properties
     pub waypoints: Option<Vec<Waypoint>>
-}
\ No newline at end of file
+}
+impl NearestResponse {
+    /// The waypoints explicitly (stable) sorted by increasing distance to the 
+    /// input coordinate. OSRM documents its response as being sorted already, 
+    /// but this method guarantees it regardless of the server behavior.
+    pub fn sorted_by_distance(&self) -> Vec<&Waypoint> {
+        let mut sorted = self.waypoints.iter().flatten().collect::<Vec<_>>();
+        sorted.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        sorted
+    }
+    /// The waypoint nearest to the input coordinate (if any)
+    pub fn nearest(&self) -> Option<&Waypoint> {
+        self.waypoints.iter().flatten().min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+}
+
+impl Client {
+    /// Snaps each of the given points to the street network, returning (at most)
+    /// `number` matches per point. As the nearest service only accepts one 
+    /// coordinate per request, one request is sent per point; but at most 4 of
+    /// these are in flight simultaneously so as not to hammer the server.
+    /// 
+    /// The results are returned in the order of the points, and the failure of 
+    /// one request does not prevent the others from succeeding.
+    pub async fn nearest_many(&self, points: &[Location], number: usize, profile: TransportationMode) 
+        -> Vec<Result<NearestResponse, Error>>
+    {
+        stream::iter(points.iter().copied())
+            .map(|point| async move {
+                let request = NearestRequestBuilder::default()
+                    .profile(profile)
+                    .coordinates(point)
+                    .number(number)
+                    .build()
+                    .expect("all mandatory fields are set");
+                request.send(self).await
+            })
+            .buffered(MAX_CONCURRENT_REQUESTS)
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::{NearestResponse, NearestRequestBuilder, Client, Location, Hint, TransportationMode};
+
+    #[test]
+    fn request_bears_on_a_single_coordinate() {
+        let request = NearestRequestBuilder::default()
+            .coordinates(Location::new(4.35, 50.85))
+            .hints(vec![Hint::empty()])
+            .build()
+            .unwrap();
+        assert_eq!("http://router.project-osrm.org/nearest/v1/car/4.35,50.85", request.url(&Client::default()));
+        assert_eq!("http://router.project-osrm.org/api/osrm/nearest/v1/car/4.35,50.85", request.url(&Client::default().service_prefix("api/osrm")));
+        for base_url in ["http://localhost:5000", "http://localhost:5000/"] {
+            let client = Client::builder().base_url(base_url).version("v1").build().unwrap();
+            assert_eq!("http://localhost:5000/nearest/v1/car/4.35,50.85", request.url(&client));
+        }
+        assert!(request.validate_general_options().is_ok());
+    }
+
+    #[test]
+    fn waypoints_are_sorted_by_distance() {
+        let text = r#"
+        {"waypoints":[
+            {"hint":"a","distance":12.5,"name":"A","location":[2.29,48.85]},
+            {"hint":"b","distance":3.0, "name":"B","location":[2.29,48.85]},
+            {"hint":"c","distance":12.5,"name":"C","location":[2.29,48.85]},
+            {"hint":"d","distance":7.2, "name":"D","location":[2.29,48.85]}
+        ]}"#;
+        let response = serde_json::from_str::<NearestResponse>(text).unwrap();
+        let names = response.sorted_by_distance().iter().map(|w| w.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["B", "D", "A", "C"], names);
+        assert_eq!("B", response.nearest().unwrap().name);
+    }
+
+    #[test]
+    fn no_nearest_without_waypoints() {
+        let response = serde_json::from_str::<NearestResponse>("{}").unwrap();
+        assert!(response.sorted_by_distance().is_empty());
+        assert!(response.nearest().is_none());
+    }
+
+    #[tokio::test]
+    async fn nearest_many_snaps_every_point_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            // echoes the requested coordinate as the name of the nearest waypoint
+            loop {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    let n = stream.read(&mut request).await.unwrap();
+                    let request = String::from_utf8_lossy(&request[..n]).into_owned();
+                    let path = request.split_whitespace().nth(1).unwrap();
+                    let coord = path.trim_start_matches("/nearest/v1/foot/").split('?').next().unwrap();
+                    let body = format!(r#"{{"code":"Ok","waypoints":[{{"hint":"","distance":1.0,"name":"{coord}","location":[4.35,50.85]}}]}}"#);
+                    let response = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+                    stream.write_all(response.as_bytes()).await.unwrap();
+                });
+            }
+        });
+
+        let client = Client::default().base_url(format!("http://{address}"));
+        let points = (0..6).map(|i| Location::new(4.0 + i as f32, 50.0)).collect::<Vec<_>>();
+        let responses = client.nearest_many(&points, 1, TransportationMode::Foot).await;
+
+        let names = responses.into_iter()
+            .map(|r| r.unwrap().nearest().unwrap().name.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["4,50", "5,50", "6,50", "7,50", "8,50", "9,50"], names);
+    }
+}
@@ -0,0 +1,233 @@
+//! This module defines how the requests failing with a transient error (e.g.
+//! a throttled or overloaded server) are sent again.
+
+use std::{collections::hash_map::RandomState, future::Future, hash::{BuildHasher, Hasher}, time::Duration};
+
+use crate::{Client, Error};
+
+/// The http statuses deemed transient by default: 429 (too many requests),
+/// 500 (internal server error), 502 (bad gateway), 503 (service unavailable)
+/// and 504 (gateway timeout)
+pub const DEFAULT_RETRIABLE_STATUSES: [u16; 5] = [429, 500, 502, 503, 504];
+/// The default upper bound of the delay between two attempts
+const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Tells how the requests failing with a transient error are sent again (see
+/// `ClientBuilder::retry_policy`). A request is retried when it fails with one
+/// of the `retriable_statuses`, a timeout, a connection error or a truncated
+/// response; any other failure (e.g. a 400 answer, or a protocol error) is
+/// returned right away. As all the services of OSRM are read-only, sending a
+/// request again is harmless.
+///
+/// Before each retry, the client waits for an exponential backoff: the n-th
+/// retry waits up to `base_backoff * 2^(n-1)` (capped at `max_backoff`). The
+/// actual delay is randomly picked in the upper half of that range (jitter) so
+/// that the clients throttled at once do not all come back at once.
+///
+/// By default, no request is retried.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request is sent (the first attempt included)
+    max_attempts: usize,
+    /// The delay before the first retry
+    base_backoff: Duration,
+    /// The upper bound of the delay between two attempts
+    max_backoff: Duration,
+    /// The http statuses deemed transient
+    retriable_statuses: Vec<u16>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(1, Duration::ZERO)
+    }
+}
+
+impl RetryPolicy {
+    /// A policy sending each request at most `max_attempts` times (the first
+    /// attempt included), waiting for an exponential backoff starting at
+    /// `base_backoff` between two attempts
+    pub fn new(max_attempts: usize, base_backoff: Duration) -> Self {
+        Self {
+            max_attempts,
+            base_backoff,
+            max_backoff: DEFAULT_MAX_BACKOFF,
+            retriable_statuses: DEFAULT_RETRIABLE_STATUSES.to_vec(),
+        }
+    }
+    /// Sets the upper bound of the delay between two attempts (30 seconds by default)
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+    /// Sets the http statuses deemed transient (`DEFAULT_RETRIABLE_STATUSES` by
+    /// default). This only decides which failures are retried: any response 
+    /// failing with an http status fails with an `HttpError`, unless it is an 
+    /// OSRM error answered with a non-transient status (e.g. a 400) which fails
+    /// with a `ProtocolError`. The status of the responses received over a unix 
+    /// socket is ignored.
+    pub fn retriable_statuses(mut self, statuses: impl IntoIterator<Item = u16>) -> Self {
+        self.retriable_statuses = statuses.into_iter().collect();
+        self
+    }
+    /// The maximum number of times a request is sent (the first attempt included)
+    pub fn max_attempts(&self) -> usize {
+        self.max_attempts
+    }
+    /// Sets the maximum number of times a request is sent (the first attempt included)
+    pub(crate) fn set_max_attempts(&mut self, max_attempts: usize) {
+        self.max_attempts = max_attempts;
+    }
+    /// Whether a response with the given http status is deemed transient
+    pub(crate) fn is_retriable_status(&self, status: u16) -> bool {
+        self.retriable_statuses.contains(&status)
+    }
+    /// Whether a request which failed with the given error after `attempts`
+    /// attempts must be sent again
+    fn should_retry(&self, error: &Error, attempts: usize) -> bool {
+        if attempts >= self.max_attempts {
+            return false;
+        }
+        match error.as_reqwest().and_then(reqwest::Error::status) {
+            Some(status) => self.is_retriable_status(status.as_u16()),
+            None => error.is_retryable(),
+        }
+    }
+    /// The delay to wait for before the given retry (1 for the first retry)
+    fn backoff(&self, retry: usize) -> Duration {
+        let exponent = u32::try_from(retry.saturating_sub(1)).unwrap_or(u32::MAX).min(31);
+        let ceiling  = self.base_backoff.saturating_mul(1 << exponent).min(self.max_backoff);
+        ceiling / 2 + ceiling.mul_f64(jitter() / 2.0)
+    }
+}
+
+/// A pseudo random number in [0, 1), good enough to spread the retries apart
+fn jitter() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1_u64 << 53) as f64
+}
+
+/// Sends a request (with `send`) until it succeeds, fails with an error which
+/// is not transient, or the retry policy of the client is exhausted.
+pub(crate) async fn retrying<T, F: Future<Output = Result<T, Error>>>(client: &Client, mut send: impl FnMut() -> F) -> Result<T, Error> {
+    let policy = &client.retry_policy;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match send().await {
+            Err(e) if policy.should_retry(&e, attempts) => {
+                let backoff = policy.backoff(attempts);
+                if !backoff.is_zero() {
+                    tokio::time::sleep(backoff).await;
+                }
+            },
+            outcome => return outcome
+        }
+    }
+}
+/// Same as `retrying`, but blocks the current thread (feature `blocking`)
+#[cfg(feature = "blocking")]
+pub(crate) fn retrying_blocking<T>(client: &Client, mut send: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+    let policy = &client.retry_policy;
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match send() {
+            Err(e) if policy.should_retry(&e, attempts) => std::thread::sleep(policy.backoff(attempts)),
+            outcome => return outcome
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::RetryPolicy;
+    use crate::{Client, Error, Location, NearestRequestBuilder, OsrmStatus};
+
+    #[test]
+    fn backoff_grows_exponentially_up_to_the_cap() {
+        let policy = RetryPolicy::new(10, Duration::from_millis(100)).max_backoff(Duration::from_millis(500));
+        for (retry, ceiling) in [(1, 100), (2, 200), (3, 400), (4, 500), (64, 500)] {
+            let backoff = policy.backoff(retry);
+            assert!(backoff >= Duration::from_millis(ceiling / 2), "{retry}: {backoff:?}");
+            assert!(backoff <= Duration::from_millis(ceiling), "{retry}: {backoff:?}");
+        }
+        assert_eq!(Duration::ZERO, RetryPolicy::default().backoff(1));
+    }
+    #[test]
+    fn only_transient_failures_are_retried() {
+        let policy = RetryPolicy::new(3, Duration::ZERO);
+        let truncated = Error::IncompleteResponse("eof".to_string());
+        assert!(policy.should_retry(&truncated, 1));
+        assert!(policy.should_retry(&truncated, 2));
+        assert!(!policy.should_retry(&truncated, 3));
+        assert!(!policy.should_retry(&Error::MissingData("routes"), 1));
+        assert!(!RetryPolicy::default().should_retry(&truncated, 1));
+    }
+
+    /// Answers the successive connections with the given statuses, the last one
+    /// being a successful nearest response
+    async fn serve(statuses: &'static [u16]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for status in statuses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await.unwrap();
+                let body = match status {
+                    200 => r#"{"code":"Ok","waypoints":[{"hint":"","distance":1.0,"name":"Rue Haute","location":[4.35,50.85]}]}"#,
+                    400 => r#"{"code":"InvalidQuery","message":"Query string malformed"}"#,
+                    _   => "<html>busy</html>",
+                };
+                let response = format!("HTTP/1.1 {status} Whatever\r\nconnection: close\r\ncontent-type: text/html\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        format!("http://{address}")
+    }
+    fn nearest() -> crate::NearestRequest {
+        NearestRequestBuilder::default().coordinates(Location::new(4.35, 50.85)).build().unwrap()
+    }
+
+    #[tokio::test]
+    async fn transient_statuses_are_retried_with_backoff() {
+        let client = Client::builder()
+            .base_url(serve(&[429, 502, 200]).await)
+            .retry_policy(RetryPolicy::new(3, Duration::from_millis(20)))
+            .build()
+            .unwrap();
+        let start = Instant::now();
+        let response = nearest().send(&client).await.unwrap();
+        assert_eq!("Rue Haute", response.nearest().unwrap().name);
+        assert!(start.elapsed() >= Duration::from_millis(10 + 20));
+        assert_eq!(3, client.metrics().requests);
+    }
+    #[tokio::test]
+    async fn other_statuses_fail_fast() {
+        let client = Client::builder()
+            .base_url(serve(&[503, 200]).await)
+            .retry_policy(RetryPolicy::new(3, Duration::ZERO).retriable_statuses([429]))
+            .build()
+            .unwrap();
+        let error = nearest().send(&client).await.unwrap_err();
+        assert_eq!(Some(503), error.as_reqwest().and_then(|e| e.status()).map(|s| s.as_u16()));
+        assert_eq!(1, client.metrics().requests);
+    }
+    #[tokio::test]
+    async fn osrm_errors_are_interpreted() {
+        let client = Client::default().base_url(serve(&[400]).await);
+        let error  = nearest().send(&client).await.unwrap_err();
+        assert!(matches!(error, Error::ProtocolError { status: OsrmStatus::InvalidQuery, .. }), "{error:?}");
+    }
+    #[tokio::test]
+    async fn retries_are_off_by_default() {
+        let client = Client::default().base_url(serve(&[502, 200]).await);
+        let error  = nearest().send(&client).await.unwrap_err();
+        assert_eq!(Some(502), error.as_reqwest().and_then(|e| e.status()).map(|s| s.as_u16()));
+        assert_eq!(1, client.metrics().requests);
+    }
+}
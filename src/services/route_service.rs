@@ -1,10 +1,14 @@
 //! This module defines the protocol of the route service
+use std::{collections::HashMap, fmt, time::Instant};
+
 use displaythis::Display;
-use serde::{Serialize, Deserialize};
+use futures_util::future::join_all;
+use serde::{Serialize, Deserialize, de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor}};
+use serde_json::{Map, Value};
 
-use crate::{request, Service, WithOptions, Waypoint, Route, add_option, Geometries};
+use crate::{request, Service, RoadClass, Approach, Location, ManeuverType, DirectionChange, WithOptions, Client, Coordinates, TransportationMode, Waypoint, Route, RouteLeg, RouteStep, Annotation, Meters, Seconds, OsrmStatus, add_option, Geometries, Error, validate_waypoints, execute_raw, json_error, to_gpx};
 
-request!(RouteRequest (Service::Route) -> RouteResponse {
+request!(#[validate] RouteRequest (Service::Route) -> RouteResponse {
     /// Search for alternative routes. Passing a number alternatives=n searches for up to n alternative routes.
     /// Please note that even if alternative routes are requested, a result cannot be guaranteed.
     #[builder(default, setter(into, strip_option))]
@@ -28,7 +32,12 @@ request!(RouteRequest (Service::Route) -> RouteResponse {
     /// Treats input coordinates indicated by given indices as waypoints in returned Match object. 
     /// Default is to treat all input coordinates as waypoints.
     #[builder(default, setter(into, strip_option))]
-    waypoints: Option<Vec<usize>>
+    waypoints: Option<Vec<usize>>,
+    /// Applies the same approach to all coordinates: the value is broadcast to
+    /// as many approaches as there are coordinates when the request is sent. 
+    /// This cannot be combined with `approaches`.
+    #[builder(default, setter(into, strip_option))]
+    approach_all: Option<Approach>
 });
 
 impl WithOptions for RouteRequest {
@@ -42,22 +51,427 @@ impl WithOptions for RouteRequest {
         add_option!(opt       opts, overview,          self.overview);
         add_option!(          opts, continue_straight, self.continue_straight);
         add_option!(opt multi opts, waypoints,         self.waypoints);
+        if let (Some(approach), Ok(n)) = (self.approach_all, self.coordinates.count()) {
+            add_option!(multi opts, approaches, &vec![approach; n]);
+        }
 
         opts
     }
+
+    fn validate(&self) -> Result<(), Error> {
+        if self.approach_all.is_some() && self.approaches.is_some() {
+            return Err(Error::InvalidRequest("approach_all cannot be combined with approaches".to_string()));
+        }
+        validate_waypoints(self.waypoints.as_ref(), &self.coordinates)
+    }
+}
+impl RouteRequest {
+    /// A copy of this request bearing on the given coordinates. This lets you
+    /// configure the options once and reuse them for many requests which only
+    /// differ by their coordinates. The hints are dropped as these designate
+    /// the previous coordinates; whereas the other per-coordinate options (e.g.
+    /// `bearings`, `radiuses`, `approaches`, `waypoints`) are kept as is: these
+    /// must hence fit the new coordinates.
+    pub fn with_coordinates(&self, coordinates: Coordinates) -> RouteRequest {
+        RouteRequest { coordinates, hints: None, ..self.clone() }
+    }
+    /// Sends the request and hands the steps of the routes over to `step_cb` as
+    /// soon as they are parsed, instead of materializing all of them in memory.
+    /// The callback receives the position of the step along with the step itself.
+    /// 
+    /// The returned response comprises everything but the steps (the `steps` of 
+    /// all legs are always empty). Note that OSRM answers with one single json
+    /// document: the raw body is still buffered, but the parsed steps never are.
+    /// This is what keeps memory in check for the turn-by-turn processing of 
+    /// very long routes.
+    pub async fn send_route_streaming<F>(&self, client: &Client, step_cb: F) -> Result<RouteResponse, Error>
+    where F: FnMut(StepPosition, RouteStep)
+    {
+        self.validate()?;
+        self.validate_general_options()?;
+
+        let mut options = self.options();
+        self.add_general_options(client, &mut options);
+
+        let start   = Instant::now();
+        let outcome = match execute_raw(client, Service::Route, &self.coordinates, &self.url(client), &options).await {
+            Ok(body) => parse_streaming(&body, step_cb),
+            Err(e)   => Err(e),
+        };
+        client.record(&outcome, start.elapsed());
+        outcome
+    }
+}
+
+impl RouteRequestBuilder {
+    /// Checks that at least one alternative is requested (`UpTo(0)` is meaningless)
+    fn validate(&self) -> Result<(), String> {
+        match self.alternatives {
+            Some(Some(AlternativesRequest::UpTo(0))) => Err("at least one alternative must be requested".to_string()),
+            _ => Ok(())
+        }
+    }
+    /// Presets the options required by turn-by-turn navigation: the steps, the
+    /// node annotations and the full overview geometry, encoded as `polyline6`.
+    /// The response is then best consumed through `RouteResponse::navigation_route`.
+    pub fn navigation(&mut self) -> &mut Self {
+        self.steps(true)
+            .annotations(RouteAnnotationRequest::Nodes)
+            .overview(OverviewRequest::Full)
+            .geometries(Geometries::Polyline6)
+    }
+    /// Avoids the toll roads. This only works if the profile of the backend
+    /// defines the `toll` class (as the stock car profile does): otherwise 
+    /// OSRM rejects the request.
+    pub fn avoid_tolls(&mut self) -> &mut Self {
+        self.exclude_class(RoadClass::Toll)
+    }
+    /// Avoids the ferries. This only works if the profile of the backend 
+    /// defines the `ferry` class (as the stock car profile does): otherwise 
+    /// OSRM rejects the request.
+    pub fn avoid_ferries(&mut self) -> &mut Self {
+        self.exclude_class(RoadClass::Ferry)
+    }
+    /// Adds the given class to the ones excluded by the request (unless it already is)
+    fn exclude_class(&mut self, class: RoadClass) -> &mut Self {
+        let class = class.to_string();
+        let mut classes = self.exclude.clone().flatten().unwrap_or_default();
+        if !classes.contains(&class) {
+            classes.push(class);
+        }
+        self.exclude(classes)
+    }
+}
+
+/// The position of a step streamed by `RouteRequest::send_route_streaming`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StepPosition {
+    /// The index of the route (among the alternatives)
+    pub route: usize,
+    /// The index of the leg within that route
+    pub leg: usize,
+    /// The index of the step within that leg
+    pub step: usize,
+}
+
+/// Parses a route response, streaming its steps to `step_cb`
+fn parse_streaming<F>(body: &[u8], step_cb: F) -> Result<RouteResponse, Error>
+where F: FnMut(StepPosition, RouteStep)
+{
+    let mut de = serde_json::Deserializer::from_slice(body);
+    let (code, message, response) = StreamingRoutes { step_cb }.deserialize(&mut de).map_err(json_error)?;
+    de.end().map_err(json_error)?;
+
+    match code {
+        OsrmStatus::Ok => Ok(response),
+        status => Err(Error::ProtocolError { status, message })
+    }
+}
+
+/// Visits a complete route response without ever keeping its steps
+struct StreamingRoutes<F> {
+    step_cb: F
+}
+impl <'de, F> DeserializeSeed<'de> for StreamingRoutes<F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = (OsrmStatus, Option<String>, RouteResponse);
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl <'de, F> Visitor<'de> for StreamingRoutes<F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = (OsrmStatus, Option<String>, RouteResponse);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a route service response")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut code = None;
+        let mut message = None;
+        let mut response = RouteResponse { waypoints: vec![], routes: vec![] };
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "code"      => code = Some(map.next_value()?),
+                "message"   => message = map.next_value()?,
+                "waypoints" => response.waypoints = map.next_value()?,
+                "routes"    => response.routes = map.next_value_seed(Routes { step_cb: &mut self.step_cb })?,
+                _           => { map.next_value::<IgnoredAny>()?; },
+            }
+        }
+        let code = code.ok_or_else(|| de::Error::missing_field("code"))?;
+        Ok((code, message, response))
+    }
+}
+
+/// Visits the routes of a response, one at a time
+struct Routes<'a, F> {
+    step_cb: &'a mut F,
+}
+impl <'de, 'a, F> DeserializeSeed<'de> for Routes<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Vec<Route>;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for Routes<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Vec<Route>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of routes")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut routes = vec![];
+        while let Some(route) = seq.next_element_seed(RouteFields { route: routes.len(), step_cb: &mut *self.step_cb })? {
+            routes.push(route);
+        }
+        Ok(routes)
+    }
+}
+
+/// Visits one route, streaming the steps of its legs
+struct RouteFields<'a, F> {
+    route: usize,
+    step_cb: &'a mut F,
+}
+impl <'de, 'a, F> DeserializeSeed<'de> for RouteFields<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Route;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for RouteFields<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Route;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a route")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = Map::new();
+        let mut legs   = vec![];
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "legs" {
+                legs = map.next_value_seed(Legs { route: self.route, step_cb: &mut *self.step_cb })?;
+            } else {
+                fields.insert(key, map.next_value()?);
+            }
+        }
+        fields.insert("legs".to_string(), Value::Array(vec![]));
+        let mut route: Route = serde_json::from_value(Value::Object(fields)).map_err(de::Error::custom)?;
+        route.legs = legs;
+        Ok(route)
+    }
+}
+
+/// Visits the legs of one route, one at a time
+struct Legs<'a, F> {
+    route: usize,
+    step_cb: &'a mut F,
 }
+impl <'de, 'a, F> DeserializeSeed<'de> for Legs<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Vec<RouteLeg>;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for Legs<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = Vec<RouteLeg>;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of route legs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut legs = vec![];
+        loop {
+            let leg = LegFields { route: self.route, leg: legs.len(), step_cb: &mut *self.step_cb };
+            match seq.next_element_seed(leg)? {
+                Some(leg) => legs.push(leg),
+                None      => return Ok(legs),
+            }
+        }
+    }
+}
+
+/// Visits one leg, handing its steps over to the callback one at a time
+struct LegFields<'a, F> {
+    route: usize,
+    leg: usize,
+    step_cb: &'a mut F,
+}
+impl <'de, 'a, F> DeserializeSeed<'de> for LegFields<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = RouteLeg;
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for LegFields<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = RouteLeg;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a route leg")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut fields = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "steps" {
+                map.next_value_seed(Steps { route: self.route, leg: self.leg, step_cb: &mut *self.step_cb })?;
+            } else {
+                fields.insert(key, map.next_value()?);
+            }
+        }
+        fields.insert("steps".to_string(), Value::Array(vec![]));
+        serde_json::from_value(Value::Object(fields)).map_err(de::Error::custom)
+    }
+}
+
+/// Visits the steps of one leg, handing them over to the callback one at a time
+struct Steps<'a, F> {
+    route: usize,
+    leg: usize,
+    step_cb: &'a mut F,
+}
+impl <'de, 'a, F> DeserializeSeed<'de> for Steps<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for Steps<'a, F> 
+where F: FnMut(StepPosition, RouteStep)
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of route steps")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut step = 0;
+        while let Some(route_step) = seq.next_element::<RouteStep>()? {
+            (self.step_cb)(StepPosition { route: self.route, leg: self.leg, step }, route_step);
+            step += 1;
+        }
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Computes the route through the given coordinates for every mode of 
+    /// transportation (see `TransportationMode::all`). The requests are sent
+    /// concurrently and each of them succeeds or fails on its own.
+    pub async fn route_all_profiles(&self, coords: Coordinates) -> HashMap<TransportationMode, Result<RouteResponse, Error>> {
+        let requests = TransportationMode::all().map(|profile| {
+            let coords = coords.clone();
+            async move {
+                let request = RouteRequestBuilder::default()
+                    .profile(profile)
+                    .coordinates(coords)
+                    .build()
+                    .expect("all mandatory fields are set");
+                (profile, request.send(self).await)
+            }
+        });
+        join_all(requests).await.into_iter().collect()
+    }
+}
+
+/// Whether (and how many) alternative routes are searched for. In any case, 
+/// OSRM may find fewer alternatives than requested (see `RouteResponse::alternatives_returned`).
 #[derive(Debug, Display, Clone, Copy)]
 pub enum AlternativesRequest {
     #[display("false")]
     NoAlternative,
+    /// Searches for alternative routes without any guaranteed cap on their
+    /// number (the actual limit is decided by the server)
     #[display("true")]
     AllAlternatives,
+    /// Searches for up to n (>= 1) alternative routes
     #[display("{0}")]
     UpTo(usize)
 }
 
-#[derive(Debug, Display, Clone, Copy)]
+/// A route requested with the navigation preset (see `RouteRequestBuilder::navigation`)
+#[derive(Debug, Clone, Copy)]
+pub struct NavigationRoute<'a> {
+    /// The underlying route
+    pub route: &'a Route,
+}
+impl NavigationRoute<'_> {
+    /// The instructions to follow along the route, in order (one per step of
+    /// each leg, from the departure to the arrival)
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.route.legs.iter()
+            .flat_map(|leg| leg.steps.iter())
+            .map(|step| Instruction {
+                location:      step.maneuver.location,
+                maneuver_type: step.maneuver.maneuver_type.clone(),
+                modifier:      step.maneuver.modifier.clone(),
+                exit:          step.maneuver.exit,
+                road:          step.road_label(),
+                distance:      step.distance,
+                duration:      step.duration,
+            })
+            .collect()
+    }
+    /// The full geometry of the route (encoded as `polyline6` by the navigation preset)
+    pub fn path(&self) -> Result<Vec<Location>, Error> {
+        self.route.geometry.decode_as(Geometries::Polyline6)
+    }
+}
+
+/// One maneuver of a navigation route (see `NavigationRoute::instructions`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Instruction {
+    /// Where the maneuver takes place
+    pub location: Location,
+    /// The kind of maneuver
+    pub maneuver_type: ManeuverType,
+    /// The direction change of the maneuver (if any)
+    pub modifier: Option<DirectionChange>,
+    /// The exit to take on a roundabout (if any)
+    pub exit: Option<u8>,
+    /// The road taken by the maneuver (see `RouteStep::road_label`)
+    pub road: String,
+    /// The distance to travel until the next maneuver
+    pub distance: Meters,
+    /// The time it takes to reach the next maneuver
+    pub duration: Seconds,
+}
+
+#[derive(Debug, Display, Clone, Copy, Default)]
 pub enum RouteAnnotationRequest {
+    #[default]
     #[display("false")]
     NoAnnotation,
     #[display("true")]
@@ -75,12 +489,10 @@ pub enum RouteAnnotationRequest {
     #[display("speed")]
     Speed,
 }
-impl Default for RouteAnnotationRequest {
-    fn default() -> Self { RouteAnnotationRequest::NoAnnotation }
-}
 
-#[derive(Debug, Display, Clone, Copy)]
+#[derive(Debug, Display, Clone, Copy, Default)]
 pub enum OverviewRequest {
+    #[default]
     #[display("false")]
     NoOverview,
     #[display("simplified")]
@@ -88,9 +500,6 @@ pub enum OverviewRequest {
     #[display("full")]
     Full,
 }
-impl Default for OverviewRequest {
-    fn default() -> Self { Self::NoOverview }
-}
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -100,14 +509,542 @@ pub struct RouteResponse {
     /// An array of Route objects, ordered by descending recommendation rank
     pub routes: Vec<Route>,
 }
+impl RouteResponse {
+    /// The waypoints delimiting the legs of the routes; that is, the waypoints 
+    /// at the indices listed in the `waypoints` option of the request (all of 
+    /// them when that option is not set). The other coordinates are merely 
+    /// passed through by the routes.
+    pub fn via_points(&self, request: &RouteRequest) -> Vec<&Waypoint> {
+        match &request.waypoints {
+            Some(indices) => indices.iter().filter_map(|i| self.waypoints.get(*i)).collect(),
+            None => self.waypoints.iter().collect(),
+        }
+    }
+    /// The indices (among the coordinates of the request) of the two waypoints 
+    /// each leg of the routes connects, in order. When the `waypoints` option of
+    /// the request is set, the legs connect the consecutive indices it lists 
+    /// (the other coordinates are passed through within the legs); otherwise 
+    /// they connect each pair of consecutive coordinates.
+    pub fn leg_waypoint_pairs(&self, request: &RouteRequest) -> Vec<(usize, usize)> {
+        let indices = match &request.waypoints {
+            Some(indices) => indices.clone(),
+            None => (0..request.coordinates.count().unwrap_or(self.waypoints.len())).collect(),
+        };
+        indices.windows(2).map(|pair| (pair[0], pair[1])).collect()
+    }
+    /// Returns the indices of the waypoints whose input coordinate was snapped
+    /// farther than `max_meters` away.
+    pub fn poorly_snapped(&self, max_meters: f32) -> Vec<usize> {
+        self.waypoints.iter()
+            .enumerate()
+            .filter(|(_, w)| !w.is_well_snapped(max_meters))
+            .map(|(i, _)| i)
+            .collect()
+    }
+    /// Exports the response as a GPX document where the waypoints are `<wpt>` 
+    /// and each route is a track (`<trk>`). See `Route::to_gpx`.
+    pub fn to_gpx(&self, geometries: Geometries) -> Result<String, Error> {
+        to_gpx(&self.waypoints, &self.routes.iter().collect::<Vec<_>>(), geometries)
+    }
+    /// The recommended route viewed as a sequence of navigation instructions. 
+    /// This assumes the request was configured with `RouteRequestBuilder::navigation`.
+    /// None when no route was found.
+    pub fn navigation_route(&self) -> Option<NavigationRoute<'_>> {
+        self.routes.first().map(|route| NavigationRoute { route })
+    }
+    /// The number of alternative routes returned in addition to the recommended one
+    pub fn alternatives_returned(&self) -> usize {
+        self.routes.len().saturating_sub(1)
+    }
+    /// The route (among the alternatives) which minimizes the given key. When 
+    /// several routes share the minimal key, the best ranked one is returned.
+    /// None when the response comprises no route at all.
+    pub fn best_by<F: Fn(&Route) -> f32>(&self, key: F) -> Option<&Route> {
+        self.routes.iter()
+            .map(|route| (key(route), route))
+            .reduce(|best, candidate| if candidate.0 < best.0 { candidate } else { best })
+            .map(|(_, route)| route)
+    }
+    /// The route (among the alternatives) having the shortest travel time
+    pub fn fastest(&self) -> Option<&Route> {
+        self.best_by(|route| route.duration.seconds())
+    }
+    /// The route (among the alternatives) having the shortest distance
+    pub fn shortest(&self) -> Option<&Route> {
+        self.best_by(|route| route.distance.meters())
+    }
+    /// Checks that every leg of every route comes with the annotation arrays 
+    /// that were requested. This catches the mistake of requesting one annotation
+    /// (say `nodes`) while reading another (say `distance`) which would otherwise
+    /// silently be empty.
+    pub fn validate_annotations(&self, requested: RouteAnnotationRequest) -> Result<(), Error> {
+        for leg in self.routes.iter().flat_map(|route| route.legs.iter()) {
+            if let Some(missing) = missing_annotation(leg.annotation.as_ref(), requested) {
+                return Err(Error::MissingData(missing));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of the requested annotation which is missing from the given one (if any)
+fn missing_annotation(annotation: Option<&Annotation>, requested: RouteAnnotationRequest) -> Option<&'static str> {
+    use RouteAnnotationRequest::*;
+    let annotation = match (annotation, requested) {
+        (_, NoAnnotation)     => return None,
+        (None, _)             => return Some("annotation"),
+        (Some(annotation), _) => annotation,
+    };
+    let distance    = || annotation.distance.is_empty().then_some("distance annotation");
+    let duration    = || annotation.duration.is_empty().then_some("duration annotation");
+    let datasources = || annotation.datasources.is_empty().then_some("datasources annotation");
+    let nodes       = || annotation.nodes.is_none().then_some("nodes annotation");
+    let weight      = || annotation.weight.is_empty().then_some("weight annotation");
+    let speed       = || annotation.speed.is_empty().then_some("speed annotation");
+    match requested {
+        NoAnnotation   => None,
+        AllAnnotations => distance().or_else(duration).or_else(datasources).or_else(nodes).or_else(weight).or_else(speed),
+        Distance       => distance(),
+        Duration       => duration(),
+        Datasources    => datasources(),
+        Nodes          => nodes(),
+        Weight         => weight(),
+        Speed          => speed(),
+    }
+}
 
 #[cfg(test)]
 mod test {
-    use crate::RouteResponse;
+    use super::parse_streaming;
+    use crate::{RouteResponse, RouteRequestBuilder, Radius, Approach, BearingRequest, ManeuverType, RouteAnnotationRequest, AlternativesRequest, Hint, StepPosition, OsrmStatus, Client, RouteRequestBuilderError, TransportationMode, Coordinates, Location, WithOptions, Error, Meters, Seconds, Geometries};
+
+    #[test]
+    fn approach_all_is_broadcast_to_all_coordinates() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .approach_all(Approach::Curb)
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+        assert!(request.options().contains(&("approaches", "curb;curb;curb".to_string())));
+    }
+    #[test]
+    fn approach_all_conflicts_with_approaches() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .approach_all(Approach::Curb)
+            .approaches(vec![Approach::Curb, Approach::Unrestricted, Approach::Curb])
+            .build()
+            .unwrap();
+        assert!(matches!(request.validate(), Err(Error::InvalidRequest(_))));
+    }
+    #[test]
+    fn with_coordinates_keeps_the_options() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .profile(TransportationMode::Bike)
+            .steps(true)
+            .alternatives(AlternativesRequest::UpTo(2))
+            .hints(vec![Hint::empty(), Hint::empty(), Hint::empty()])
+            .build()
+            .unwrap();
+        let other = request.with_coordinates(Coordinates::Multi(vec![Location::new(4.35, 50.85), Location::new(4.40, 50.80)]));
+
+        assert_eq!(request.options(), other.options());
+        assert!(other.hints.is_none());
+        assert_eq!("http://router.project-osrm.org/route/v1/bike/4.35,50.85;4.4,50.8", other.url(&Client::default()));
+    }
+    #[test]
+    fn avoid_tolls_and_ferries() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .avoid_tolls()
+            .avoid_ferries()
+            .avoid_tolls()
+            .build()
+            .unwrap();
+        let mut options = vec![];
+        request.add_general_options(&Client::default(), &mut options);
+        assert!(options.contains(&("exclude", "toll,ferry".to_string())), "{options:?}");
+    }
+    #[test]
+    fn default_radius_is_broadcast_to_all_coordinates() {
+        let client  = Client::default().default_radius(Radius::Limited(10.0));
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .build()
+            .unwrap();
+        let mut options = vec![];
+        request.add_general_options(&client, &mut options);
+        assert!(options.contains(&("radiuses", "10;10;10".to_string())), "{options:?}");
+    }
+    #[test]
+    fn request_radiuses_override_the_default_radius() {
+        let client  = Client::default().default_radius(Radius::Limited(10.0));
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .radiuses(vec![Radius::Unlimited, Radius::Limited(5.0), Radius::Unlimited])
+            .build()
+            .unwrap();
+        let mut options = vec![];
+        request.add_general_options(&client, &mut options);
+        let radiuses = options.iter().filter(|(name, _)| *name == "radiuses").collect::<Vec<_>>();
+        assert_eq!(vec![&("radiuses", "unlimited;5;unlimited".to_string())], radiuses);
+    }
+    #[test]
+    fn avoid_tolls_keeps_other_excluded_classes() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .exclude(vec!["motorway".to_string()])
+            .avoid_tolls()
+            .build()
+            .unwrap();
+        let mut options = vec![];
+        request.add_general_options(&Client::default(), &mut options);
+        assert!(options.contains(&("exclude", "motorway,toll".to_string())), "{options:?}");
+    }
+
+    fn three_coordinates() -> Coordinates {
+        Coordinates::Multi(vec![
+            Location::new(4.516091, 50.859136), 
+            Location::new(4.55,     50.75), 
+            Location::new(4.621039, 50.668585)
+        ])
+    }
+
+    #[test]
+    fn waypoints_must_include_first_and_last_coordinates() {
+        let validate = |waypoints: Vec<usize>| RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .waypoints(waypoints)
+            .build()
+            .unwrap()
+            .validate();
+        
+        assert!(validate(vec![0, 2]).is_ok());
+        assert!(validate(vec![0, 1, 2]).is_ok());
+        assert!(matches!(validate(vec![1, 2]),    Err(Error::InvalidWaypoints(_))));
+        assert!(matches!(validate(vec![0, 1]),    Err(Error::InvalidWaypoints(_))));
+        assert!(matches!(validate(vec![]),        Err(Error::InvalidWaypoints(_))));
+        assert!(matches!(validate(vec![0, 5, 2]), Err(Error::InvalidWaypoints(_))));
+    }
+
+    #[test]
+    fn via_points_are_aligned_with_requested_waypoints() {
+        let response = serde_json::from_str::<RouteResponse>(THREE_WAYPOINTS_RESPONSE).unwrap();
+        let request  = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .waypoints(vec![0, 2])
+            .build()
+            .unwrap();
+        assert!(request.validate().is_ok());
+        let names = response.via_points(&request).iter().map(|w| w.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(vec!["Jagersstraat", "Voie Minckelers"], names);
+        assert_eq!(3, response.via_points(&RouteRequestBuilder::default().coordinates(three_coordinates()).build().unwrap()).len());
+    }
+    const THREE_WAYPOINTS_RESPONSE: &str = r#"
+        {"code":"Ok",
+         "routes":[],
+         "waypoints":[
+             {"hint":"", "distance":7.615206, "name":"Jagersstraat", "location":[4.516091,50.859136]},
+             {"hint":"", "distance":12.5, "name":"Chaussée de Wavre", "location":[4.55,50.75]},
+             {"hint":"", "distance":72.232413, "name":"Voie Minckelers", "location":[4.621039,50.668585]}
+         ]
+        }"#;
+
+    #[test]
+    fn leg_waypoint_pairs_follow_the_requested_waypoints() {
+        let response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        let request  = RouteRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.5, 50.8); 5]))
+            .waypoints(vec![0, 2, 4])
+            .build()
+            .unwrap();
+        assert_eq!(vec![(0, 2), (2, 4)], response.leg_waypoint_pairs(&request));
+    }
+    #[test]
+    fn leg_waypoint_pairs_connect_consecutive_coordinates_by_default() {
+        let response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        let request  = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .build()
+            .unwrap();
+        assert_eq!(vec![(0, 1), (1, 2)], response.leg_waypoint_pairs(&request));
+    }
+
+    #[test]
+    fn request_profile_overrides_client_default_profile() {
+        let client = Client::default().default_profile(TransportationMode::Bike);
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .build()
+            .unwrap();
+        assert!(request.url(&Client::default()).contains("/v1/car/"));
+        assert!(request.url(&client).contains("/v1/bike/"));
+
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .profile(TransportationMode::Foot)
+            .build()
+            .unwrap();
+        assert!(request.url(&client).contains("/v1/foot/"));
+    }
+
+    #[tokio::test]
+    async fn route_all_profiles_yields_one_outcome_per_profile() {
+        let client   = Client::default().base_url("http://127.0.0.1:1".to_string());
+        let outcomes = client.route_all_profiles(three_coordinates()).await;
+        assert_eq!(3, outcomes.len());
+        for profile in TransportationMode::all() {
+            assert!(matches!(outcomes[&profile], Err(Error::HttpError(_))));
+        }
+    }
+
+    #[test]
+    fn hints_must_align_with_coordinates() {
+        let validate = |hints: Vec<Hint>| RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .hints(hints)
+            .build()
+            .unwrap()
+            .validate_general_options();
+        let hint = || serde_json::from_str::<Hint>(r#""-0eQgNlS0oMEAAAAEwAAACwAAAA8AAAA""#).unwrap();
+
+        assert!(validate(vec![hint(), Hint::empty(), hint()]).is_ok());
+        assert!(matches!(validate(vec![hint(), hint()]), 
+            Err(Error::OptionLengthMismatch { option: "hints", expected: 3, actual: 2 })));
+    }
+    #[test]
+    fn per_coordinate_options_must_align_with_coordinates() {
+        let builder = || {
+            let mut builder = RouteRequestBuilder::default();
+            builder.coordinates(three_coordinates());
+            builder
+        };
+        let bearing = BearingRequest { value: 90, range: 20 };
+        let valid = builder()
+            .bearings(vec![bearing; 3])
+            .radiuses(vec![Radius::Unlimited; 3])
+            .approaches(vec![Approach::Curb; 3])
+            .build()
+            .unwrap();
+        assert!(valid.validate_general_options().is_ok());
+
+        let request = builder().bearings(vec![bearing; 2]).build().unwrap();
+        assert!(matches!(request.validate_general_options(),
+            Err(Error::OptionLengthMismatch { option: "bearings", expected: 3, actual: 2 })));
+        let request = builder().radiuses(vec![Radius::Unlimited; 4]).build().unwrap();
+        assert!(matches!(request.validate_general_options(),
+            Err(Error::OptionLengthMismatch { option: "radiuses", expected: 3, actual: 4 })));
+        let request = builder().approaches(vec![Approach::Unrestricted]).build().unwrap();
+        assert!(matches!(request.validate_general_options(),
+            Err(Error::OptionLengthMismatch { option: "approaches", expected: 3, actual: 1 })));
+    }
+
+    #[test]
+    fn streaming_hands_steps_over_to_the_callback() {
+        let mut names = vec![];
+        let response = parse_streaming(STEPS_RESPONSE.as_bytes(), |position, step| names.push((position, step.name))).unwrap();
+
+        assert_eq!(names, vec![
+            (StepPosition { route: 0, leg: 0, step: 0 }, "Jagersstraat".to_string()),
+            (StepPosition { route: 0, leg: 0, step: 1 }, "Voie Minckelers".to_string()),
+        ]);
+        assert_eq!(1, response.routes.len());
+        assert_eq!(1, response.routes[0].legs.len());
+        assert!(response.routes[0].legs[0].steps.is_empty());
+        assert_eq!(Meters(28139.9), response.routes[0].legs[0].distance);
+        assert_eq!("Jagersstraat", response.waypoints[0].name);
+    }
+    #[test]
+    fn streaming_reports_protocol_errors() {
+        let outcome = parse_streaming(br#"{"code":"NoRoute","message":"Impossible route"}"#, |_, _| ());
+        assert!(matches!(outcome, Err(Error::ProtocolError { status: OsrmStatus::NoRoute, message: Some(m) }) if m == "Impossible route"));
+    }
+    #[test]
+    fn streaming_reports_truncated_bodies() {
+        let truncated = &STEPS_RESPONSE.as_bytes()[..STEPS_RESPONSE.len() / 2];
+        assert!(matches!(parse_streaming(truncated, |_, _| ()), Err(Error::IncompleteResponse(_))));
+        assert!(matches!(parse_streaming(br#"{"code":"Ok","routes":12}"#, |_, _| ()), Err(Error::JsonError(_))));
+    }
+
+    const STEPS_RESPONSE: &str = r#"
+        {"code":"Ok",
+         "routes":[
+           {"geometry":"slluHq`qZ~eChbD",
+            "legs":[
+               {"steps":[
+                  {"distance":152.3, "duration":20.1, "weight":20.1, "geometry":"slluHq`qZ~eChbD", "name":"Jagersstraat", "mode":"driving", "intersections":[],
+                   "maneuver":{"location":[4.516091,50.859136],"bearing_before":0,"bearing_after":90,"type":"depart"}},
+                  {"distance":0.0, "duration":0.0, "weight":0.0, "geometry":"slluHq`qZ", "name":"Voie Minckelers", "mode":"driving", "intersections":[],
+                   "maneuver":{"location":[4.621039,50.668585],"bearing_before":90,"bearing_after":0,"type":"arrive"}}
+                ],
+                "summary":"", "weight":1519.3, "duration":1498.1, "distance":28139.9}
+            ],
+            "weight_name":"routability", "weight":1519.3, "duration":1498.1, "distance":28139.9}
+         ],
+         "waypoints":[
+             {"hint":"", "distance":7.615206, "name":"Jagersstraat", "location":[4.516091,50.859136]},
+             {"hint":"", "distance":72.232413, "name":"Voie Minckelers", "location":[4.621039,50.668585]}
+         ]
+        }"#;
+
+    #[test]
+    fn navigation_preset_options() {
+        let request = RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .navigation()
+            .build()
+            .unwrap();
+        let options = request.options();
+        assert!(options.contains(&("steps", "true".to_string())));
+        assert!(options.contains(&("annotations", "nodes".to_string())));
+        assert!(options.contains(&("overview", "full".to_string())));
+        assert!(options.contains(&("geometries", "polyline6".to_string())));
+    }
+    #[test]
+    fn navigation_route_instructions() {
+        let response = serde_json::from_str::<RouteResponse>(STEPS_RESPONSE).unwrap();
+        let instructions = response.navigation_route().unwrap().instructions();
+        assert_eq!(2, instructions.len());
+        assert_eq!(ManeuverType::Depart, instructions[0].maneuver_type);
+        assert_eq!("Jagersstraat", instructions[0].road);
+        assert_eq!(Meters(152.3), instructions[0].distance);
+        assert_eq!(ManeuverType::Arrive, instructions[1].maneuver_type);
+        assert_eq!(Location::new(4.621039, 50.668585), instructions[1].location);
+    }
+    #[test]
+    fn navigation_route_geometries_use_polyline6() {
+        let response = serde_json::from_str::<RouteResponse>(NAVIGATION_RESPONSE).unwrap();
+        let (depart, arrive) = (Location::new(4.516091, 50.859136), Location::new(4.621039, 50.668585));
+        assert_eq!(vec![depart, arrive], response.navigation_route().unwrap().path().unwrap());
+
+        let route = &response.routes[0];
+        assert_eq!("LINESTRING(4.516091 50.859135, 4.621039 50.668587)", route.to_wkt(Geometries::Polyline6).unwrap());
+        let segments = route.legs[0].annotated_segments(Geometries::Polyline6).unwrap();
+        assert_eq!(1, segments.len());
+        assert_eq!((depart, arrive), (segments[0].from, segments[0].to));
+        assert_eq!(Some(Seconds(1498.1)), segments[0].duration);
+        assert_eq!(Some(18.7), segments[0].speed);
+    }
+    const NAVIGATION_RESPONSE: &str = r#"
+        {"code":"Ok",
+         "routes":[
+           {"geometry":"_ge_`BunsrGldsJg~kE",
+            "legs":[
+               {"steps":[
+                  {"distance":28139.9, "duration":1498.1, "weight":1519.3, "geometry":"_ge_`BunsrGldsJg~kE", "name":"Jagersstraat", "mode":"driving", "intersections":[],
+                   "maneuver":{"location":[4.516091,50.859136],"bearing_before":0,"bearing_after":90,"type":"depart"}},
+                  {"distance":0.0, "duration":0.0, "weight":0.0, "geometry":"qaqs_B}m`yG", "name":"Voie Minckelers", "mode":"driving", "intersections":[],
+                   "maneuver":{"location":[4.621039,50.668585],"bearing_before":90,"bearing_after":0,"type":"arrive"}}
+                ],
+                "annotation":{"nodes":[1,2], "duration":[1498.1], "speed":[18.7]},
+                "summary":"", "weight":1519.3, "duration":1498.1, "distance":28139.9}
+            ],
+            "weight_name":"routability", "weight":1519.3, "duration":1498.1, "distance":28139.9}
+         ],
+         "waypoints":[
+             {"hint":"", "distance":7.615206, "name":"Jagersstraat", "location":[4.516091,50.859136]},
+             {"hint":"", "distance":72.232413, "name":"Voie Minckelers", "location":[4.621039,50.668585]}
+         ]
+        }"#;
+    #[test]
+    fn speed_annotation_is_serialized() {
+        assert_eq!("speed", RouteAnnotationRequest::Speed.to_string());
+
+        let request = RouteRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.516091, 50.859136), Location::new(4.621039, 50.668585)]))
+            .annotations(RouteAnnotationRequest::Speed)
+            .build()
+            .unwrap();
+        assert!(request.options().contains(&("annotations", "speed".to_string())));
+    }
 
     #[test]
     fn parse_response() {
-        let text = r#"
+        let response = serde_json::from_str::<RouteResponse>(RESPONSE);
+        assert!(response.is_ok());
+    }
+
+    #[test]
+    fn poorly_snapped_lists_waypoints_beyond_threshold() {
+        let response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        assert!(response.poorly_snapped(100.0).is_empty());
+        assert_eq!(vec![1], response.poorly_snapped(10.0));
+        assert_eq!(vec![0, 1], response.poorly_snapped(5.0));
+    }
+
+    #[test]
+    fn validate_annotations_reports_missing_arrays() {
+        let mut response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        assert!(response.validate_annotations(RouteAnnotationRequest::NoAnnotation).is_ok());
+        assert!(matches!(response.validate_annotations(RouteAnnotationRequest::Nodes), Err(Error::MissingData("annotation"))));
+
+        response.routes[0].legs[0].annotation = serde_json::from_str(r#"{"nodes":[1,2,3]}"#).unwrap();
+        assert!(response.validate_annotations(RouteAnnotationRequest::Nodes).is_ok());
+        assert!(matches!(response.validate_annotations(RouteAnnotationRequest::Distance), Err(Error::MissingData("distance annotation"))));
+        assert!(matches!(response.validate_annotations(RouteAnnotationRequest::AllAnnotations), Err(Error::MissingData(_))));
+    }
+
+    #[test]
+    fn up_to_zero_alternatives_is_invalid() {
+        let build = |alternatives: AlternativesRequest| RouteRequestBuilder::default()
+            .coordinates(three_coordinates())
+            .alternatives(alternatives)
+            .build();
+        assert!(matches!(build(AlternativesRequest::UpTo(0)), Err(RouteRequestBuilderError::ValidationError(_))));
+        assert!(build(AlternativesRequest::UpTo(1)).is_ok());
+        assert!(build(AlternativesRequest::AllAlternatives).is_ok());
+    }
+    #[test]
+    fn alternatives_returned_excludes_the_recommended_route() {
+        assert_eq!(2, serde_json::from_str::<RouteResponse>(ALTERNATIVES).unwrap().alternatives_returned());
+        let mut response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        assert_eq!(0, response.alternatives_returned());
+        response.routes.clear();
+        assert_eq!(0, response.alternatives_returned());
+    }
+
+    #[test]
+    fn fastest_and_shortest_among_alternatives() {
+        let response = serde_json::from_str::<RouteResponse>(ALTERNATIVES).unwrap();
+        assert_eq!(Seconds(1450.2), response.fastest().unwrap().duration);
+        assert_eq!(Meters(27013.5), response.shortest().unwrap().distance);
+        assert_eq!(1519.3, response.best_by(|r| -r.weight).unwrap().weight);
+        assert_eq!(Seconds(1498.1), response.best_by(|_| 0.0).unwrap().duration);
+    }
+    #[test]
+    fn no_best_route_without_routes() {
+        let mut response = serde_json::from_str::<RouteResponse>(RESPONSE).unwrap();
+        response.routes.clear();
+        assert!(response.fastest().is_none());
+        assert!(response.shortest().is_none());
+    }
+
+    const ALTERNATIVES: &str = r#"
+        {"code":"Ok",
+        "routes":[
+           {"geometry":"slluHq`qZ~eChbD",
+            "legs":[{"steps":[], "summary":"", "weight":1519.3, "duration":1498.1, "distance":28139.9}],
+            "weight_name":"routability", "weight":1519.3, "duration":1498.1, "distance":28139.9
+           },
+           {"geometry":"slluHq`qZ~eChbD",
+            "legs":[{"steps":[], "summary":"", "weight":1480.0, "duration":1450.2, "distance":29540.7}],
+            "weight_name":"routability", "weight":1480.0, "duration":1450.2, "distance":29540.7
+           },
+           {"geometry":"slluHq`qZ~eChbD",
+            "legs":[{"steps":[], "summary":"", "weight":1502.4, "duration":1601.8, "distance":27013.5}],
+            "weight_name":"routability", "weight":1502.4, "duration":1601.8, "distance":27013.5
+           }
+         ],
+         "waypoints":[
+             {"hint":"", "distance":7.615206, "name":"Jagersstraat", "location":[4.516091,50.859136]},
+             {"hint":"", "distance":72.232413, "name":"Voie Minckelers", "location":[4.621039,50.668585]}
+         ]
+       }
+        "#;
+
+    const RESPONSE: &str = r#"
         {"code":"Ok",
         "routes":[
            {"geometry":"slluHq`qZ~eChbDtcFfzCpzAulD~vBsfAbh@}j@|cAs~CxpCkoDtuA}sE|f@wcAxiAi{@nbB{n@jMd_@bk@i]xCvLyL|GjH`O",
@@ -139,7 +1076,4 @@ mod test {
          ]
        }   
         "#;
-        let response = serde_json::from_str::<RouteResponse>(text);
-        assert!(response.is_ok());
-    }
 }
\ No newline at end of file
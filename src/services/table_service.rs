@@ -5,8 +5,10 @@
 //! Note that the distances are not the shortest distance between two coordinates,
 //! but rather the distances of the fastest routes. Duration is in seconds and 
 //! distances is in meters.
+use std::{fmt, time::Instant};
+
 use displaythis::Display;
-use serde::{Serialize, Deserialize};
+use serde::{Serialize, Deserialize, de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor}};
 
 use crate::*;
 
@@ -41,22 +43,176 @@ impl WithOptions for TableRequest {
         add_option!(opt multi opts, sources,             self.sources);
         add_option!(opt multi opts, destinations,        self.destinations);
         add_option!(opt       opts, annotations,         self.annotations);
-        add_option!(opt       opts, fallback_speed,      self.fallback_speed);
+        add_option!(opt       opts, fallback_speed,      self.fallback_speed.map(Decimal));
         add_option!(opt       opts, fallback_coordinate, self.fallback_coordinate);
-        add_option!(opt       opts, scale_factor,        self.scale_factor);
+        add_option!(opt       opts, scale_factor,        self.scale_factor.map(Decimal));
         opts
     }
 }
 
+impl TableRequest {
+    /// Sends the request and hands the rows of the durations/distances matrices
+    /// over to `row_cb` as soon as they are parsed, instead of materializing the
+    /// complete matrices in memory. The callback receives the matrix the row 
+    /// belongs to, the index of the row (source) and the row itself.
+    /// 
+    /// The returned response comprises everything but the matrices (`durations`
+    /// and `distances` are always None). Note that OSRM answers with one single
+    /// json document: the raw body is still buffered, but the parsed matrices 
+    /// never are. This is what keeps memory in check for very large tables.
+    pub async fn send_streaming<F>(&self, client: &Client, row_cb: F) -> Result<TableResponse, Error>
+    where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+    {
+        self.validate()?;
+        self.validate_general_options()?;
+
+        let mut options = self.options();
+        self.add_general_options(client, &mut options);
+
+        let start   = Instant::now();
+        let outcome = match execute_raw(client, Service::Table, &self.coordinates, &self.url(client), &options).await {
+            Ok(body) => parse_streaming(&body, row_cb),
+            Err(e)   => Err(e),
+        };
+        client.record(&outcome, start.elapsed());
+        outcome
+    }
+}
+
+impl Client {
+    /// Computes the matrix of the durations (in seconds) of the fastest routes
+    /// between all pairs of the given locations. This is a thin convenience over
+    /// `TableRequestBuilder` for the most common use of the table service; use the
+    /// builder whenever you need any other option.
+    pub async fn quick_table(&self, coords: Vec<Location>, profile: TransportationMode) -> Result<Vec<Vec<Option<f32>>>, Error> {
+        let request = TableRequestBuilder::default()
+            .profile(profile)
+            .coordinates(Coordinates::Multi(coords))
+            .build()
+            .expect("all mandatory fields are set");
+
+        request.send(self).await?
+            .durations
+            .ok_or(Error::MissingData("durations"))
+    }
+}
+
+/// Identifies the matrix a row streamed by `TableRequest::send_streaming` belongs to
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum TableMatrix {
+    #[display("durations")]
+    Durations,
+    #[display("distances")]
+    Distances,
+}
+
+/// Parses a table response, streaming the rows of its matrices to `row_cb`
+fn parse_streaming<F>(body: &[u8], row_cb: F) -> Result<TableResponse, Error>
+where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+{
+    let mut de = serde_json::Deserializer::from_slice(body);
+    let (code, message, response) = StreamingTable { row_cb }.deserialize(&mut de).map_err(json_error)?;
+    de.end().map_err(json_error)?;
+
+    match code {
+        OsrmStatus::Ok => Ok(response),
+        status => Err(Error::ProtocolError { status, message })
+    }
+}
+
+/// Visits a complete table response without ever keeping its matrices
+struct StreamingTable<F> {
+    row_cb: F
+}
+impl <'de, F> DeserializeSeed<'de> for StreamingTable<F> 
+where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+{
+    type Value = (OsrmStatus, Option<String>, TableResponse);
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+impl <'de, F> Visitor<'de> for StreamingTable<F> 
+where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+{
+    type Value = (OsrmStatus, Option<String>, TableResponse);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a table service response")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut code = None;
+        let mut message = None;
+        let mut response = TableResponse {
+            durations: None,
+            distances: None,
+            sources: vec![],
+            destinations: vec![],
+            fallback_speed_cells: None,
+        };
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "code"                 => code = Some(map.next_value()?),
+                "message"              => message = map.next_value()?,
+                "sources"              => response.sources = map.next_value()?,
+                "destinations"         => response.destinations = map.next_value()?,
+                "fallback_speed_cells" => response.fallback_speed_cells = map.next_value()?,
+                "durations"            => map.next_value_seed(MatrixRows { matrix: TableMatrix::Durations, row_cb: &mut self.row_cb })?,
+                "distances"            => map.next_value_seed(MatrixRows { matrix: TableMatrix::Distances, row_cb: &mut self.row_cb })?,
+                _                      => { map.next_value::<IgnoredAny>()?; },
+            }
+        }
+        let code = code.ok_or_else(|| de::Error::missing_field("code"))?;
+        Ok((code, message, response))
+    }
+}
+
+/// Visits the rows of one matrix, handing them over to the callback one at a time
+struct MatrixRows<'a, F> {
+    matrix: TableMatrix,
+    row_cb: &'a mut F,
+}
+impl <'de, 'a, F> DeserializeSeed<'de> for MatrixRows<'a, F> 
+where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+{
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+impl <'de, 'a, F> Visitor<'de> for MatrixRows<'a, F> 
+where F: FnMut(TableMatrix, usize, Vec<Option<f32>>)
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a matrix (array of rows)")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let mut index = 0;
+        while let Some(row) = seq.next_element::<Vec<Option<f32>>>()? {
+            (self.row_cb)(self.matrix, index, row);
+            index += 1;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TableResponse {
     /// array of arrays that stores the matrix in row-major order. durations[i][j] gives the travel
     /// time from the i-th source to the j-th destination. Values are given in seconds. 
     /// Can be null if no route between i and j can be found.
+    /// Only present when the durations were requested (see `TableAnnotationRequest::includes_durations`)
     pub durations: Option<Vec<Vec<Option<f32>>>>,
     /// array of arrays that stores the matrix in row-major order. distances[i][j] gives the travel 
     /// distance from the i-th source to the j-th destination. Values are given in meters. 
     /// Can be null if no route between i and j can be found.
+    /// Only present when the distances were requested (see `TableRequestBuilder::require_distances`)
     pub distances: Option<Vec<Vec<Option<f32>>>>,
     /// array of Waypoint objects describing all sources in order
     pub sources: Vec<Waypoint>,
@@ -66,32 +222,276 @@ pub struct TableResponse {
     /// values based on fallback_speed. Will be absent if fallback_speed is not used.
     pub fallback_speed_cells: Option<Vec<(usize, usize)>>,
 }
+impl TableResponse {
+    /// The index (among the coordinates of the request) of the location used as
+    /// the `i`-th source; that is, the location the `i`-th row of the matrices 
+    /// starts from. None when there is no such row.
+    pub fn source_input_index(&self, request: &TableRequest, i: usize) -> Option<usize> {
+        input_index(request.sources.as_ref(), self.sources.len(), i)
+    }
+    /// The index (among the coordinates of the request) of the location used as
+    /// the `j`-th destination; that is, the location the `j`-th column of the 
+    /// matrices leads to. None when there is no such column.
+    pub fn destination_input_index(&self, request: &TableRequest, j: usize) -> Option<usize> {
+        input_index(request.destinations.as_ref(), self.destinations.len(), j)
+    }
+    /// Tells whether the durations matrix is symmetric; that is, whether going
+    /// from A to B takes as long as going from B to A (give or take `tolerance`
+    /// seconds) for all pairs of locations. An asymmetry typically reveals one 
+    /// way streets or turn restrictions. Two unreachable cells (null) are deemed
+    /// equal, whereas a null cell never equals a duration.
+    /// 
+    /// Symmetry only makes sense when the sources and the destinations are the
+    /// same locations (in the same order): None is returned otherwise, as well
+    /// as when the durations were not requested.
+    pub fn is_symmetric(&self, tolerance: f32) -> Option<bool> {
+        let durations = self.durations.as_ref()?;
+        let same_locations = self.sources.len() == self.destinations.len() 
+            && self.sources.iter().zip(self.destinations.iter()).all(|(s, d)| s.location == d.location);
+        if !same_locations || durations.len() != self.sources.len() {
+            return None;
+        }
+        let cell = |i: usize, j: usize| durations[i].get(j).copied().flatten();
+        let symmetric = (0..durations.len()).all(|i| (0..i).all(|j| match (cell(i, j), cell(j, i)) {
+            (Some(a), Some(b)) => (a - b).abs() <= tolerance,
+            (None, None) => true,
+            _ => false,
+        }));
+        Some(symmetric)
+    }
+}
+
+/// Maps the index of a row (column) back to the index of the coordinate it 
+/// stands for, given the subset of coordinates used as sources (destinations).
+fn input_index(subset: Option<&Vec<usize>>, len: usize, index: usize) -> Option<usize> {
+    match subset {
+        Some(subset) => subset.get(index).copied(),
+        None if index < len => Some(index),
+        None => None,
+    }
+}
 
 
-#[derive(Debug, Display, Clone, Copy)]
+#[derive(Debug, Display, Clone, Copy, Default)]
 pub enum TableAnnotationRequest {
     #[display("distance")]
     Distance,
+    #[default]
     #[display("duration")]
     Duration,
     #[display("duration,distance")]
     Both,
 }
-impl Default for TableAnnotationRequest {
-    fn default() -> Self { TableAnnotationRequest::Duration }
+impl TableAnnotationRequest {
+    /// True iff the `durations` matrix is populated in the response
+    pub fn includes_durations(self) -> bool {
+        matches!(self, Self::Duration | Self::Both)
+    }
+    /// True iff the `distances` matrix is populated in the response
+    pub fn includes_distances(self) -> bool {
+        matches!(self, Self::Distance | Self::Both)
+    }
+}
+
+impl TableRequestBuilder {
+    /// Computes the durations/distances from the coordinate at `origin_index` 
+    /// to all coordinates (that is, a matrix made of one single row)
+    pub fn one_to_many(&mut self, origin_index: usize) -> &mut Self {
+        self.sources(vec![origin_index])
+    }
+    /// Computes the durations/distances from all coordinates to the one at 
+    /// `dest_index` (that is, a matrix made of one single column)
+    pub fn many_to_one(&mut self, dest_index: usize) -> &mut Self {
+        self.destinations(vec![dest_index])
+    }
+    /// Makes sure the `distances` matrix is part of the response, in addition 
+    /// to whatever other matrix was requested through the annotations. Remember
+    /// that only the `durations` are returned by default.
+    pub fn require_distances(&mut self) -> &mut Self {
+        let requested = self.annotations.flatten().unwrap_or_default();
+        if requested.includes_distances() {
+            self
+        } else {
+            self.annotations(TableAnnotationRequest::Both)
+        }
+    }
 }
 
 /// When using a fallback_speed , use the user-supplied coordinate ( input ), 
 /// or the snapped location ( snapped ) for calculating distances.
-#[derive(Debug, Display, Clone, Copy)]
+#[derive(Debug, Display, Clone, Copy, Default)]
 pub enum FallbackCoordinateRequest {
     /// Use the user supplied coordinate for calculating distances
+    #[default]
     #[display("input")]
     UserSupplied,
     /// Use the snapped location for calculating distances.
     #[display("snapped")]
     Snapped,
 }
-impl Default for FallbackCoordinateRequest {
-    fn default() -> Self { FallbackCoordinateRequest::UserSupplied }
-}
\ No newline at end of file
+
+
+#[cfg(test)]
+mod test {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::{TableMatrix, TableResponse, TableAnnotationRequest, Error, OsrmStatus, TableRequestBuilder, Client, Coordinates, Location, WithOptions};
+    use super::parse_streaming;
+
+    #[tokio::test]
+    async fn large_tables_are_posted() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buffer  = vec![0; 4096];
+            while !String::from_utf8_lossy(&request).contains("annotations=duration") {
+                let n = stream.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..n]);
+            }
+            let body = r#"{"code":"Ok","durations":[[0.0]],"sources":[],"destinations":[]}"#;
+            let response = format!("HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request).into_owned()
+        });
+
+        let client  = Client::default().base_url(format!("http://{address}")).post_threshold(100);
+        let request = TableRequestBuilder::default()
+            .coordinates(Coordinates::Multi((0..20).map(|i| Location::new(4.35, 50.0 + i as f32 / 10.0)).collect()))
+            .annotations(TableAnnotationRequest::Duration)
+            .build()
+            .unwrap();
+        request.send(&client).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /table/v1/car HTTP/1.1"), "{request}");
+        assert!(request.contains("coordinates=4.35%2C50%3B4.35%2C50.1%3B"), "{request}");
+    }
+
+    #[test]
+    fn numeric_options_use_a_dot_as_decimal_separator() {
+        let request = TableRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.5, 50.85), Location::new(4.62, 50.67)]))
+            .fallback_speed(13.88)
+            .scale_factor(0.5)
+            .build()
+            .unwrap();
+        let options = request.options();
+        assert!(options.contains(&("fallback_speed", "13.88".to_string())));
+        assert!(options.contains(&("scale_factor",   "0.5".to_string())));
+    }
+
+    #[test]
+    fn require_distances_extends_the_requested_annotations() {
+        let annotations = |builder: &mut TableRequestBuilder| builder
+            .coordinates(Coordinates::Multi(vec![Location::new(4.5, 50.85), Location::new(4.62, 50.67)]))
+            .require_distances()
+            .build()
+            .unwrap()
+            .options()
+            .into_iter()
+            .find(|(name, _)| *name == "annotations")
+            .map(|(_, value)| value);
+        assert_eq!(Some("duration,distance".to_string()), annotations(&mut TableRequestBuilder::default()));
+        assert_eq!(Some("duration,distance".to_string()), annotations(TableRequestBuilder::default().annotations(TableAnnotationRequest::Duration)));
+        assert_eq!(Some("distance".to_string()),          annotations(TableRequestBuilder::default().annotations(TableAnnotationRequest::Distance)));
+    }
+
+    #[test]
+    fn one_to_many_and_many_to_one() {
+        let options = |builder: &mut TableRequestBuilder| builder
+            .coordinates(Coordinates::Multi(vec![Location::new(4.5, 50.85), Location::new(4.55, 50.75), Location::new(4.62, 50.67)]))
+            .build()
+            .unwrap()
+            .options();
+
+        let one_to_many = options(TableRequestBuilder::default().one_to_many(1));
+        assert!(one_to_many.contains(&("sources", "1".to_string())));
+        assert!(!one_to_many.iter().any(|(name, _)| *name == "destinations"));
+
+        let many_to_one = options(TableRequestBuilder::default().many_to_one(2));
+        assert!(many_to_one.contains(&("destinations", "2".to_string())));
+        assert!(!many_to_one.iter().any(|(name, _)| *name == "sources"));
+    }
+
+    #[test]
+    fn matrix_indices_map_back_to_input_indices() {
+        let response = serde_json::from_str::<TableResponse>(RESPONSE).unwrap();
+        let request  = TableRequestBuilder::default()
+            .coordinates(Coordinates::Multi(vec![Location::new(4.5, 50.85), Location::new(4.55, 50.75), Location::new(4.62, 50.67)]))
+            .sources(vec![2, 0])
+            .build()
+            .unwrap();
+        assert_eq!(Some(2), response.source_input_index(&request, 0));
+        assert_eq!(Some(0), response.source_input_index(&request, 1));
+        assert_eq!(None,    response.source_input_index(&request, 2));
+        assert_eq!(Some(1), response.destination_input_index(&request, 1));
+        assert_eq!(None,    response.destination_input_index(&request, 2));
+    }
+
+    const RESPONSE: &str = r#"
+    {"code":"Ok",
+     "distances":[[0,1200.5],[1190.1,0]],
+     "durations":[[0,120.3],[118.9,null]],
+     "sources":[
+        {"hint":"abc","distance":1.5,"name":"Jagersstraat","location":[4.516091,50.859136]},
+        {"hint":"def","distance":2.5,"name":"Voie Minckelers","location":[4.621039,50.668585]}
+     ],
+     "destinations":[
+        {"hint":"abc","distance":1.5,"name":"Jagersstraat","location":[4.516091,50.859136]},
+        {"hint":"def","distance":2.5,"name":"Voie Minckelers","location":[4.621039,50.668585]}
+     ]
+    }"#;
+
+    #[test]
+    fn it_detects_asymmetric_durations() {
+        let response = serde_json::from_str::<TableResponse>(RESPONSE).unwrap();
+        assert_eq!(Some(true),  response.is_symmetric(1.5));
+        assert_eq!(Some(false), response.is_symmetric(1.0));
+
+        let mut response = response;
+        response.durations.as_mut().unwrap()[1][0] = None;
+        assert_eq!(Some(false), response.is_symmetric(1000.0));
+    }
+    #[test]
+    fn symmetry_requires_same_sources_and_destinations() {
+        let mut response = serde_json::from_str::<TableResponse>(RESPONSE).unwrap();
+        response.destinations.reverse();
+        assert_eq!(None, response.is_symmetric(1.5));
+
+        let mut response = serde_json::from_str::<TableResponse>(RESPONSE).unwrap();
+        response.durations = None;
+        assert_eq!(None, response.is_symmetric(1.5));
+    }
+
+    #[test]
+    fn it_streams_the_rows_of_all_matrices() {
+        let mut rows = vec![];
+        let response = parse_streaming(RESPONSE.as_bytes(), |matrix, i, row| rows.push((matrix, i, row))).unwrap();
+
+        assert!(response.durations.is_none());
+        assert!(response.distances.is_none());
+        assert_eq!(2, response.sources.len());
+        assert_eq!(2, response.destinations.len());
+        assert_eq!(rows, vec![
+            (TableMatrix::Distances, 0, vec![Some(0.0), Some(1200.5)]),
+            (TableMatrix::Distances, 1, vec![Some(1190.1), Some(0.0)]),
+            (TableMatrix::Durations, 0, vec![Some(0.0), Some(120.3)]),
+            (TableMatrix::Durations, 1, vec![Some(118.9), None]),
+        ]);
+    }
+
+    #[test]
+    fn it_reports_protocol_errors() {
+        let response = parse_streaming(br#"{"code":"NoTable","message":"no table"}"#, |_, _, _| {});
+        assert!(matches!(response, Err(Error::ProtocolError { status: OsrmStatus::NoTable, message: Some(m) }) if m == "no table"));
+    }
+
+    #[test]
+    fn it_reports_truncated_bodies() {
+        let truncated = &RESPONSE.as_bytes()[..RESPONSE.len() / 2];
+        let response  = parse_streaming(truncated, |_, _, _| {});
+        assert!(matches!(response, Err(Error::IncompleteResponse(_))));
+    }
+}
@@ -39,16 +39,21 @@
 //! type          |  string  | the type of this turn - values like turn , continue , etc. See the StepManeuver for a partial list, this field also exposes internal turn types that are never returned with an API response
 //! modifier      |  string  | the direction modifier of the turn ( left , sharp left , etc)
 //!
+use std::time::Instant;
+
 use bytes::Bytes;
+use futures_util::{Stream, StreamExt, stream};
+use reqwest::header::HeaderMap;
 
 use derive_builder::Builder;
 use crate::*;
 
 #[derive(Debug, Clone, Builder)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct TileRequest {
-    /// Mode of transportation
-    #[builder(default="crate::TransportationMode::Car")]
-    profile: crate::TransportationMode,
+    /// Mode of transportation (defaults to the `default_profile` of the client)
+    #[builder(default, setter(into, strip_option))]
+    profile: Option<crate::TransportationMode>,
     /// X goes from 0 (left edge is 180 °W) to 2zoom − 1 (right edge is 180 °E)
     x: f32,
     /// Y goes from 0 (top edge is 85.0511 °N) to 2zoom − 1 (bottom edge is 85.0511 °S) in a Mercator projection
@@ -58,41 +63,308 @@ pub struct TileRequest {
     zoom: usize
 }
 
+/// The lowest zoom level at which OSRM serves tiles
+pub const MIN_TILE_ZOOM: usize = 12;
+
+impl TileRequestBuilder {
+    /// A builder for the request of the tile comprising the given location at
+    /// the given zoom level (see `lonlat_to_tile`). The profile may still be 
+    /// set before building the request.
+    /// 
+    /// Fails with a validation error when the zoom is below `MIN_TILE_ZOOM`, 
+    /// since OSRM refuses to serve such tiles.
+    pub fn from_lat_lon(latitude: f32, longitude: f32, zoom: usize) -> Result<Self, TileRequestBuilderError> {
+        check_zoom(zoom)?;
+        let (x, y) = lonlat_to_tile(longitude, latitude, zoom);
+        let mut builder = Self::default();
+        builder.x(x as f32).y(y as f32).zoom(zoom);
+        Ok(builder)
+    }
+    /// Checks that OSRM serves the tile: the zoom must be at least `MIN_TILE_ZOOM`
+    /// and the x and y indices must range from 0 to 2^zoom - 1.
+    fn validate(&self) -> Result<(), String> {
+        let Some(zoom) = self.zoom else {
+            return Ok(()); // the missing field is reported by build
+        };
+        check_zoom(zoom)?;
+        let tiles = 2_f64.powi(i32::try_from(zoom).unwrap_or(i32::MAX));
+        for (name, index) in [("x", self.x), ("y", self.y)] {
+            match index {
+                Some(index) if !(0.0..tiles).contains(&f64::from(index)) => 
+                    return Err(format!("tile {name} must range from 0 to {} at zoom {zoom} (got {index})", tiles - 1.0)),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fails when OSRM does not serve the tiles of the given zoom level
+fn check_zoom(zoom: usize) -> Result<(), String> {
+    if zoom < MIN_TILE_ZOOM {
+        Err(format!("OSRM only serves tiles from zoom {MIN_TILE_ZOOM} (got {zoom})"))
+    } else {
+        Ok(())
+    }
+}
+
+/// The (x, y) indices of the slippy map tile comprising the location at the 
+/// given longitude and latitude, at the given zoom level (standard web mercator
+/// tiling, see https://wiki.openstreetmap.org/wiki/Slippy_map_tilenames).
+pub fn lonlat_to_tile(longitude: f32, latitude: f32, zoom: usize) -> (u32, u32) {
+    tile_of(Location::new(longitude, latitude), zoom)
+}
+
 impl TileRequest {
     pub async fn send(&self, client: &crate::Client) -> Result<Bytes, crate::Error> {
-        let response = client.reqwest.get(self.url(client))
-            .send()
-            .await?
-            .bytes()
-            .await?;
+        let (response, _headers) = self.send_with_headers(client).await?;
         Ok(response)
     }
+    /// Sends the request and returns the tile along with the http headers of 
+    /// the response (e.g. rate-limit headers set by a gateway)
+    pub async fn send_with_headers(&self, client: &crate::Client) -> Result<(Bytes, HeaderMap), crate::Error> {
+        let url = self.url(client);
+        crate::retrying(client, || async {
+            let start   = Instant::now();
+            let outcome = client.fetch(&url, &[]).await
+                .map(|(headers, tile)| (tile, headers));
+            client.record(&outcome, start.elapsed());
+            outcome
+        }).await
+    }
     pub async fn debug(&self, client: &crate::Client) -> Result<String, crate::Error> {
-        let response = client.reqwest.get(self.url(client))
-            .send()
-            .await?
-            .text()
-            .await?;
-        Ok(response)
+        let (_headers, body) = client.fetch(&self.url(client), &[]).await?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+    /// Downloads the tile, blocking the current thread (feature `blocking`)
+    #[cfg(feature = "blocking")]
+    pub fn send_blocking(&self, client: &crate::BlockingClient) -> Result<Bytes, crate::Error> {
+        let config = client.config();
+        let url    = self.url(config);
+        crate::retrying_blocking(config, || {
+            let start   = Instant::now();
+            let outcome = client.fetch(&url, &[]).map(|(_headers, tile)| tile);
+            config.record(&outcome, start.elapsed());
+            outcome
+        })
+    }
+    /// Same as `debug`, but blocks the current thread (feature `blocking`)
+    #[cfg(feature = "blocking")]
+    pub fn debug_blocking(&self, client: &crate::BlockingClient) -> Result<String, crate::Error> {
+        let (_headers, body) = client.fetch(&self.url(client.config()), &[])?;
+        Ok(String::from_utf8_lossy(&body).into_owned())
     }
 
     pub fn url(&self, client: &crate::Client) -> String {
         let base    = &client.base_url;
+        let prefix  = &client.service_prefix;
         let version = &client.version;
         let service = Service::Tile;
-        let profile = self.profile;
+        let profile = self.profile.unwrap_or(client.default_profile).as_str();
         let x = self.x;
         let y = self.y;
         let zoom = self.zoom;
 
-        format!("{base}/{service}/{version}/{profile}/tile({x},{y},{zoom}).mvt")
+        format!("{base}{prefix}/{service}/{version}/{profile}/tile({x},{y},{zoom}).mvt")
     }
 
+    /// The url of the OSRM debug map centered on the requested tile. The map 
+    /// expects its position as `#zoom/latitude/longitude`.
     pub fn show_url(&self) -> String {
-        let zoom = self.zoom;
-        let latitude = self.x;
-        let longitude = self.y;
-        format!("http://map.project-osrm.org/debug/#{zoom}/{latitude}/{longitude}")
+        let zoom   = self.zoom;
+        let center = tile_center(self.x, self.y, zoom);
+        format!("http://map.project-osrm.org/debug/#{zoom}/{:.5}/{:.5}", center.latitude, center.longitude)
+    }
+}
+
+impl Client {
+    /// Downloads all the tiles covering the bounding box delimited by its south 
+    /// west (`min`) and north east (`max`) corners at the given zoom level. This
+    /// is typically useful to prefetch a map area.
+    /// 
+    /// The tiles are yielded along with their (x, y) indices as soon as they are
+    /// downloaded, hence not necessarily in order. At most 4 tiles are downloaded
+    /// simultaneously so as not to hammer the server. Below `MIN_TILE_ZOOM`, 
+    /// every tile fails with an `InvalidRequest` error.
+    pub fn tiles_for_bbox(&self, min: Location, max: Location, zoom: usize, profile: TransportationMode) 
+        -> impl Stream<Item = Result<(u32, u32, Bytes), Error>> + '_
+    {
+        stream::iter(tiles_covering(min, max, zoom))
+            .map(move |(x, y)| async move {
+                let request = TileRequestBuilder::default()
+                    .profile(profile)
+                    .x(x as f32)
+                    .y(y as f32)
+                    .zoom(zoom)
+                    .build()
+                    .map_err(|e| Error::InvalidRequest(e.to_string()))?;
+                let tile = request.send(self).await?;
+                Ok((x, y, tile))
+            })
+            .buffer_unordered(MAX_CONCURRENT_REQUESTS)
+    }
+}
+
+/// The (x, y) indices of the tiles covering the bounding box delimited by its 
+/// south west (`min`) and north east (`max`) corners at the given zoom level.
+/// The indices are computed lazily as a large box comprises a great many tiles.
+fn tiles_covering(min: Location, max: Location, zoom: usize) -> impl Iterator<Item = (u32, u32)> {
+    let (min_x, max_y) = tile_of(min, zoom);
+    let (max_x, min_y) = tile_of(max, zoom);
+    (min_x..=max_x).flat_map(move |x| (min_y..=max_y).map(move |y| (x, y)))
+}
+/// The (x, y) indices of the tile comprising the given location
+fn tile_of(location: Location, zoom: usize) -> (u32, u32) {
+    let zoom = u8::try_from(zoom).unwrap_or(u8::MAX);
+    let (x, y) = location.to_pixel(zoom);
+    let last = 2_f64.powi(i32::from(zoom)) - 1.0;
+    let index = |pixel: f64| (pixel / 256.0).floor().clamp(0.0, last) as u32;
+    (index(x), index(y))
+}
+
+/// The location at the center of the tile having the given (x, y) indices
+fn tile_center(x: f32, y: f32, zoom: usize) -> Location {
+    let tiles = 2_f64.powi(i32::try_from(zoom).unwrap_or(i32::MAX));
+    let longitude = (f64::from(x) + 0.5) / tiles * 360.0 - 180.0;
+    let mercator  = std::f64::consts::PI * (1.0 - 2.0 * (f64::from(y) + 0.5) / tiles);
+    let latitude  = mercator.sinh().atan().to_degrees();
+    Location::new(longitude as f32, latitude as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use crate::{Client, Error, Location, OsrmStatus, TileRequestBuilder, TileRequestBuilderError, lonlat_to_tile};
+    use super::{tiles_covering, tile_of, tile_center};
+
+    #[tokio::test]
+    async fn tiles_respect_the_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        // the server accepts the connection but never answers
+        let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let client = Client::default()
+            .base_url(format!("http://{address}"))
+            .with_timeout(Duration::from_millis(100));
+        let request = TileRequestBuilder::default().x(2096.0).y(1373.0).zoom(12).build().unwrap();
+        let error = request.send(&client).await.unwrap_err();
+        assert!(error.as_reqwest().is_some_and(|e| e.is_timeout()), "{error}");
+        drop(server);
+    }
+    #[tokio::test]
+    async fn tiles_respect_the_timeout_mid_body() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        // the server sends the headers and part of the body, then stalls
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let _ = stream.read(&mut request).await.unwrap();
+            let response = "HTTP/1.1 200 OK\r\ncontent-type: application/x-protobuf\r\ncontent-length: 100\r\n\r\nabc";
+            stream.write_all(response.as_bytes()).await.unwrap();
+            stream
+        });
+
+        let client = Client::default()
+            .base_url(format!("http://{address}"))
+            .with_timeout(Duration::from_millis(100));
+        let request = TileRequestBuilder::default().x(2096.0).y(1373.0).zoom(12).build().unwrap();
+        let error = request.send(&client).await.unwrap_err();
+        assert!(error.as_reqwest().is_some_and(|e| e.is_timeout()), "{error:?}");
+        assert!(error.is_retryable());
+        #[cfg(feature = "http-status")]
+        assert_eq!(http::StatusCode::GATEWAY_TIMEOUT, error.http_status());
+        drop(server);
     }
-    
-}
\ No newline at end of file
+    #[tokio::test]
+    async fn failed_tiles_are_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address  = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let answers = [
+                ("400 Bad Request", "application/json", r#"{"code":"InvalidQuery","message":"Invalid zoom level"}"#),
+                ("404 Not Found",   "text/html",        "<html>not found</html>"),
+            ];
+            for (status, content_type, body) in answers {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut request = vec![0; 4096];
+                let _ = stream.read(&mut request).await.unwrap();
+                let response = format!("HTTP/1.1 {status}\r\nconnection: close\r\ncontent-type: {content_type}\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let client  = Client::default().base_url(format!("http://{address}"));
+        let request = TileRequestBuilder::default().x(2096.0).y(1373.0).zoom(12).build().unwrap();
+        let error = request.send(&client).await.unwrap_err();
+        assert!(matches!(error, Error::ProtocolError { status: OsrmStatus::InvalidQuery, .. }), "{error:?}");
+        let error = request.send(&client).await.unwrap_err();
+        assert_eq!(Some(404), error.as_reqwest().and_then(|e| e.status()).map(|s| s.as_u16()));
+    }
+
+    #[test]
+    fn service_prefix_is_inserted_in_the_url() {
+        let request = TileRequestBuilder::default().x(2096.0).y(1373.0).zoom(12).build().unwrap();
+        let client  = Client::default().base_url("http://gateway.local".to_string());
+        assert_eq!("http://gateway.local/tile/v1/car/tile(2096,1373,12).mvt", request.url(&client));
+        let client  = client.service_prefix("/osrm/");
+        assert_eq!("http://gateway.local/osrm/tile/v1/car/tile(2096,1373,12).mvt", request.url(&client));
+        let client  = client.service_prefix("");
+        assert_eq!("http://gateway.local/tile/v1/car/tile(2096,1373,12).mvt", request.url(&client));
+    }
+    #[test]
+    fn tiles_covering_brussels() {
+        let tiles = tiles_covering(Location::new(4.30, 50.80), Location::new(4.45, 50.90), 12).collect::<Vec<_>>();
+        assert_eq!(vec![(2096, 1373), (2096, 1374), (2097, 1373), (2097, 1374), (2098, 1373), (2098, 1374)], tiles);
+    }
+    #[test]
+    fn tiles_covering_the_whole_world() {
+        let tiles = tiles_covering(Location::new(-180.0, -89.0), Location::new(180.0, 89.0), 1).collect::<Vec<_>>();
+        assert_eq!(vec![(0, 0), (0, 1), (1, 0), (1, 1)], tiles);
+    }
+    #[test]
+    fn tiles_covering_a_point() {
+        let tiles = tiles_covering(Location::new(0.0, 0.0), Location::new(0.0, 0.0), 0).collect::<Vec<_>>();
+        assert_eq!(vec![(0, 0)], tiles);
+    }
+    #[test]
+    fn show_url_centers_on_the_tile() {
+        let request = TileRequestBuilder::default().x(1310.0).y(3166.0).zoom(13).build().unwrap();
+        assert_eq!("http://map.project-osrm.org/debug/#13/37.77071/-122.40967", request.show_url());
+    }
+    #[test]
+    fn tile_of_a_location() {
+        assert_eq!((1310, 3166), lonlat_to_tile(-122.4097, 37.7707, 13));
+        assert_eq!((2097, 1373), lonlat_to_tile(4.35, 50.85, 12));
+    }
+    #[test]
+    fn builder_from_lat_lon() {
+        let request = TileRequestBuilder::from_lat_lon(37.7707, -122.4097, 13).unwrap().build().unwrap();
+        assert_eq!("http://router.project-osrm.org/tile/v1/car/tile(1310,3166,13).mvt", request.url(&Client::default()));
+        assert!(matches!(TileRequestBuilder::from_lat_lon(37.7707, -122.4097, 11), 
+            Err(TileRequestBuilderError::ValidationError(_))));
+    }
+    #[test]
+    fn zoom_must_be_at_least_12() {
+        let build = |zoom| TileRequestBuilder::default().x(0.0).y(0.0).zoom(zoom).build();
+        assert!(matches!(build(11), Err(TileRequestBuilderError::ValidationError(message)) if message.contains("zoom 12")));
+        assert!(build(12).is_ok());
+    }
+    #[test]
+    fn tile_indices_must_exist_at_the_zoom() {
+        let build = |x, y| TileRequestBuilder::default().x(x).y(y).zoom(12).build();
+        assert!(build(4095.0, 4095.0).is_ok());
+        assert!(matches!(build(4096.0, 0.0), Err(TileRequestBuilderError::ValidationError(_))));
+        assert!(matches!(build(0.0, -1.0), Err(TileRequestBuilderError::ValidationError(_))));
+        assert!(matches!(TileRequestBuilder::default().x(0.0).zoom(12).build(), 
+            Err(TileRequestBuilderError::UninitializedField("y"))));
+    }
+    #[test]
+    fn tile_center_lies_within_the_tile() {
+        let center = tile_center(2096.0, 1373.0, 12);
+        assert_eq!((2096, 1373), tile_of(center, 12));
+    }
+}
@@ -75,30 +75,85 @@ pub struct TripResponse {
     /// An array of Route objects that assemble the trace
     pub trips: Option<Vec<Route>>,
 }
+impl TripResponse {
+    /// Exports the response as a GPX document where the waypoints are `<wpt>` 
+    /// (in input order) and each trip is a track (`<trk>`). See `Route::to_gpx`.
+    pub fn to_gpx(&self, geometries: Geometries) -> Result<String, Error> {
+        let waypoints = self.waypoints.iter().flatten().map(|w| &w.waypoint);
+        let trips = self.trips.iter().flatten().collect::<Vec<_>>();
+        to_gpx(waypoints, &trips, geometries)
+    }
+    /// The number of trips in the response
+    pub fn trip_count(&self) -> usize {
+        self.trips.as_ref().map_or(0, Vec::len)
+    }
+    /// The total distance of all the trips in the response
+    pub fn total_distance(&self) -> Meters {
+        self.trips.iter().flatten().map(|trip| trip.distance).sum()
+    }
+    /// The total duration of all the trips in the response
+    pub fn total_duration(&self) -> Seconds {
+        self.trips.iter().flatten().map(|trip| trip.duration).sum()
+    }
+    /// Tells whether the trips are closed tours; that is, whether the geometry of
+    /// each trip ends where it started. This is the case when the trip was requested 
+    /// with `roundtrip=true`, whereas trips requested with `roundtrip=false` are 
+    /// open-ended. Remember that OSRM only supports `roundtrip=false` in combination 
+    /// with `source=first` and `destination=last` (see the table of supported 
+    /// combinations in the module documentation). 
+    /// 
+    /// Returns false when the response holds no trip at all.
+    pub fn is_roundtrip(&self) -> Result<bool, Error> {
+        let trips = match &self.trips {
+            Some(trips) if !trips.is_empty() => trips,
+            _ => return Ok(false)
+        };
+        for trip in trips {
+            // the precision is irrelevant when it comes to comparing endpoints
+            let points = trip.geometry.decode_with_precision(5)?;
+            if points.is_empty() || points.first() != points.last() {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    /// The decoded path of an open trip (one requested with `roundtrip=false`),
+    /// from its first to its last location. OSRM only supports open trips when
+    /// `source=first` and `destination=last`: the path hence runs from the first
+    /// to the last input coordinate, visiting the others in the order given by 
+    /// the `waypoint_index` of the waypoints. `geometries` is the format in which
+    /// the geometry of the trip was requested.
+    /// 
+    /// This fails with `MissingData` when the response holds no trip, and with 
+    /// `InvalidRequest` when the trip is a closed tour (see `is_roundtrip`).
+    pub fn open_path(&self, geometries: Geometries) -> Result<Vec<Location>, Error> {
+        let trip = self.trips.iter().flatten().next().ok_or(Error::MissingData("trips"))?;
+        if self.is_roundtrip()? {
+            return Err(Error::InvalidRequest("the trip is a roundtrip, it has no open path".to_string()));
+        }
+        trip.geometry.decode_as(geometries)
+    }
+}
 
 /// Returned route starts at any or first coordinate (by default, any)
-#[derive(Debug, Clone, Copy, Display)]
+#[derive(Debug, Clone, Copy, Display, Default)]
 pub enum Source {
     #[display("first")]
     First,
+    #[default]
     #[display("any")]
     Any
 }
-impl Default for Source {
-    fn default() -> Self { Self::Any }
-}
 
 /// Returned route ends at any or first coordinate (by default, any)
-#[derive(Debug, Clone, Copy, Display)]
+#[derive(Debug, Clone, Copy, Display, Default)]
 pub enum Destination {
     #[display("last")]
     Last,
+    #[default]
     #[display("any")]
     Any
 }
-impl Default for Destination {
-    fn default() -> Self { Self::Any }
-}
 
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -115,7 +170,7 @@ pub struct TripWaypoint {
 
 #[cfg(test)]
 mod tests {
-    use crate::{Response, TripResponse};
+    use crate::{Response, TripResponse, Error, Geometries, Location, Meters, Seconds};
 
     #[test]
     fn it_can_parse_respone_with_geojson_geometry() {
@@ -124,7 +179,82 @@ mod tests {
         "#;
 
         let parsed = serde_json::from_str::<Response<TripResponse>>(response);
-        assert!(parsed.is_ok())
+        assert!(parsed.is_ok());
+
+        let trip: Result<TripResponse, Error> = parsed.unwrap().into();
+        assert!(trip.unwrap().is_roundtrip().unwrap());
+    }
+
+    #[test]
+    fn it_sums_up_all_trips() {
+        let response = r#"
+        {"trips":[
+            {"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":10.0,"duration":12.5,"distance":100.0},
+            {"geometry":"_p~iF~ps|U_ulLnnqC","legs":[],"weight_name":"routability","weight":20.0,"duration":30.0,"distance":250.5}
+        ],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        assert_eq!(2,     parsed.trip_count());
+        assert_eq!(Meters(350.5), parsed.total_distance());
+        assert_eq!(Seconds(42.5), parsed.total_duration());
+    }
+
+    #[test]
+    fn it_sums_up_nothing_without_trips() {
+        let parsed = serde_json::from_str::<TripResponse>("{}").unwrap();
+        assert_eq!(0,   parsed.trip_count());
+        assert_eq!(Meters(0.0), parsed.total_distance());
+        assert_eq!(Seconds(0.0), parsed.total_duration());
+    }
+
+    #[test]
+    fn it_detects_roundtrip_with_geojson_geometry() {
+        let response = r#"
+        {"trips":[{"geometry":{"coordinates":[[-1.301955,44.189087],[-1.007841,44.051348],[-1.301955,44.189087]],"type":"LineString"},"legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        assert!(parsed.is_roundtrip().unwrap());
+    }
+
+    #[test]
+    fn it_detects_open_trip_with_geojson_geometry() {
+        let response = r#"
+        {"trips":[{"geometry":{"coordinates":[[-1.301955,44.189087],[-1.007841,44.051348]],"type":"LineString"},"legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        assert!(!parsed.is_roundtrip().unwrap());
+    }
+
+    #[test]
+    fn it_decodes_the_open_path() {
+        let response = r#"
+        {"trips":[{"geometry":"_p~iF~ps|U_ulLnnqC_mqNvxq`@","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        let path = parsed.open_path(Geometries::Polyline).unwrap();
+        assert_eq!(vec![Location::new(-120.2, 38.5), Location::new(-120.95, 40.7), Location::new(-126.453, 43.252)], path);
+        let path = parsed.open_path(Geometries::Polyline6).unwrap();
+        assert_eq!(vec![Location::new(-12.02, 3.85), Location::new(-12.095, 4.07), Location::new(-12.6453, 4.3252)], path);
+    }
+
+    #[test]
+    fn it_has_no_open_path_for_roundtrips() {
+        let response = r#"
+        {"trips":[{"geometry":{"coordinates":[[-1.301955,44.189087],[-1.007841,44.051348],[-1.301955,44.189087]],"type":"LineString"},"legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        assert!(matches!(parsed.open_path(Geometries::GeoJson), Err(Error::InvalidRequest(_))));
+        let empty = serde_json::from_str::<TripResponse>("{}").unwrap();
+        assert!(matches!(empty.open_path(Geometries::GeoJson), Err(Error::MissingData("trips"))));
+    }
+
+    #[test]
+    fn it_detects_open_trip_with_string_geometry() {
+        let response = r#"
+        {"trips":[{"geometry":"_p~iF~ps|U_ulLnnqC_mqNvxq`@","legs":[],"weight_name":"routability","weight":1.0,"duration":1.0,"distance":1.0}],"waypoints":[]}
+        "#;
+        let parsed = serde_json::from_str::<TripResponse>(response).unwrap();
+        assert!(!parsed.is_roundtrip().unwrap());
     }
 
     #[test]
@@ -134,6 +264,9 @@ mod tests {
         "#;
 
         let parsed = serde_json::from_str::<Response<TripResponse>>(response);
-        assert!(parsed.is_ok())
+        assert!(parsed.is_ok());
+
+        let trip: Result<TripResponse, Error> = parsed.unwrap().into();
+        assert!(trip.unwrap().is_roundtrip().unwrap());
     }
 }
\ No newline at end of file
@@ -0,0 +1,156 @@
+//! This module lets a client reach an OSRM backend exposed over a unix domain
+//! socket (feature `unix-socket`, only available on unix platforms).
+
+use std::{path::{Path, PathBuf}, time::Duration};
+
+use bytes::Bytes;
+use hyper::Body;
+use hyperlocal::{UnixConnector, UnixClientExt};
+use reqwest::{Url, header::HeaderMap};
+
+use crate::{Client, Error, check_complete};
+
+/// The host used in the urls of the requests routed over a unix socket. It is
+/// a mere placeholder: the socket path decides where the requests go.
+const UNIX_SOCKET_HOST: &str = "http://localhost";
+
+/// The transport of the requests sent over a unix domain socket
+#[derive(Debug, Clone)]
+pub(crate) struct UnixSocket {
+    /// The path of the socket OSRM listens on
+    path: PathBuf,
+    /// The http client speaking over that socket
+    hyper: hyper::Client<UnixConnector, Body>,
+}
+
+impl Client {
+    /// Routes all the requests over the unix domain socket at the given path
+    /// instead of tcp. This is a low-overhead way to talk to an OSRM instance
+    /// running on the same machine (e.g. `osrm-routed` behind a socket proxy).
+    /// The base url is replaced by a placeholder host.
+    ///
+    /// Limitations: unix sockets only exist on unix platforms; and the settings
+    /// of the underlying http client (`user_agent`, `proxy` and 
+    /// `http2_prior_knowledge` of the `ClientBuilder`) do not apply to the 
+    /// requests sent over the socket. The timeout and the default headers apply
+    /// as usual: a request left unanswered fails with a `SocketTimeout`.
+    pub fn unix_socket(mut self, path: impl AsRef<Path>) -> Self {
+        self.base_url    = UNIX_SOCKET_HOST.to_string();
+        self.unix_socket = Some(UnixSocket { path: path.as_ref().to_path_buf(), hyper: hyper::Client::unix() });
+        self
+    }
+}
+
+impl UnixSocket {
+    /// Sends a GET request over the socket and returns the headers and body of 
+    /// the response, which must be received in full within the given `timeout` (if any)
+    pub(crate) async fn fetch(&self, url: &str, headers: &HeaderMap, options: &[(&'static str, String)], timeout: Option<Duration>) -> Result<(HeaderMap, Bytes), Error> {
+        let mut request = hyper::Request::get(hyperlocal::Uri::new(&self.path, &path_and_query(url, options)?))
+            .body(Body::empty())
+            .map_err(|e| Error::InvalidRequest(e.to_string()))?;
+        request.headers_mut().extend(headers.clone());
+
+        let exchange = async {
+            let response = self.hyper.request(request).await?;
+            let headers  = response.headers().clone();
+            let body     = hyper::body::to_bytes(response.into_body()).await?;
+            Ok::<_, Error>((headers, body))
+        };
+        let (headers, body) = match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, exchange).await
+                .map_err(|_| Error::SocketTimeout(timeout))??,
+            None => exchange.await?,
+        };
+        check_complete(&headers, &body)?;
+        Ok((headers, body))
+    }
+}
+
+/// The path and query string of the request to the given url with the given options
+fn path_and_query(url: &str, options: &[(&'static str, String)]) -> Result<String, Error> {
+    let url = Url::parse_with_params(url, options)
+        .map_err(|e| Error::InvalidRequest(e.to_string()))?;
+    match url.query() {
+        Some(query) if !query.is_empty() => Ok(format!("{}?{query}", url.path())),
+        _ => Ok(url.path().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::UnixListener};
+
+    use super::path_and_query;
+    use crate::{Client, Error, Location, NearestRequestBuilder};
+
+    #[tokio::test]
+    async fn requests_go_through_the_socket() {
+        let path = std::env::temp_dir().join(format!("osrm_client_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let n = stream.read(&mut request).await.unwrap();
+            let body = r#"{"code":"Ok","waypoints":[{"hint":"","distance":1.0,"name":"Rue Haute","location":[4.35,50.85],"nodes":[1,2]}]}"#;
+            let response = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{body}", body.len());
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..n]).into_owned()
+        });
+
+        let client = Client::default().unix_socket(&path);
+        let response = NearestRequestBuilder::default()
+            .coordinates(Location::new(4.35, 50.85))
+            .build()
+            .unwrap()
+            .send(&client)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!("Rue Haute", response.waypoints.unwrap()[0].name);
+        assert!(request.starts_with("GET /nearest/v1/car/4.35,50.85?"), "{request}");
+    }
+
+    #[tokio::test]
+    async fn requests_over_the_socket_respect_the_timeout() {
+        let path = std::env::temp_dir().join(format!("osrm_client_timeout_{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let listener = UnixListener::bind(&path).unwrap();
+        // the server accepts the connection but never answers
+        let server = tokio::spawn(async move { listener.accept().await.unwrap() });
+
+        let client = Client::default()
+            .unix_socket(&path)
+            .with_timeout(Duration::from_millis(100));
+        let error = NearestRequestBuilder::default()
+            .coordinates(Location::new(4.35, 50.85))
+            .build()
+            .unwrap()
+            .send(&client)
+            .await
+            .unwrap_err();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(matches!(error, Error::SocketTimeout(timeout) if timeout == Duration::from_millis(100)), "{error:?}");
+        assert!(error.is_retryable());
+        drop(server);
+    }
+
+    #[test]
+    fn unix_socket_uses_a_placeholder_host() {
+        let client = Client::default().unix_socket("/var/run/osrm.sock");
+        assert_eq!("http://localhost", client.base_url);
+        assert!(client.unix_socket.is_some());
+    }
+    #[test]
+    fn path_and_query_of_a_request() {
+        let options = [("steps", "true".to_string()), ("radiuses", "10;unlimited".to_string())];
+        assert_eq!("/route/v1/car/4.35,50.85;4.4,50.8?steps=true&radiuses=10%3Bunlimited",
+            path_and_query("http://localhost/route/v1/car/4.35,50.85;4.4,50.8", &options).unwrap());
+        assert_eq!("/nearest/v1/car/4.35,50.85",
+            path_and_query("http://localhost/nearest/v1/car/4.35,50.85", &[]).unwrap());
+    }
+}